@@ -0,0 +1,198 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::midi_app_with_functions::{FunctionMapper, FunctionValue};
+use eframe::egui::{self, Color32, ScrollArea, Sense, ViewportCommand};
+use midi_mapper::{
+    control_address::ControlAddress,
+    jack_connection_panel::jack_connection_panel,
+    jackmidi::{MidiMsgAdvanced, MidiMsgBase},
+    jackprocess::JackConnectionManager,
+    midi_egui_elements::{
+        midi_id_double_precision_value_indicator, midi_id_value_indicator,
+        midi_note_status_intensity_indicator,
+    },
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const FLASH_DURATION: Duration = Duration::from_millis(300);
+
+/// Last call of a function
+#[derive(Default)]
+pub struct FunctionState {
+    pub value: Option<FunctionValue>,
+    /// relative steps summed up to 0..127
+    pub relative_value: u32,
+    pub source: Option<ControlAddress>,
+    pub last_call: Option<Instant>,
+    pub num_calls: usize,
+}
+
+impl FunctionState {
+    fn call(&mut self, value: FunctionValue, source: ControlAddress) {
+        if let FunctionValue::Delta(delta) = value {
+            self.relative_value = self
+                .relative_value
+                .saturating_add_signed(delta as i32)
+                .min(127);
+        }
+        self.value = Some(value);
+        self.source = Some(source);
+        self.last_call = Some(Instant::now());
+        self.num_calls += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct FunctionMonitorGui {
+    pub midi_receiver: Option<std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>>,
+    pub midi_thread: Option<std::thread::JoinHandle<()>>,
+    pub tx_close: Option<crossbeam_channel::Sender<bool>>,
+    pub function_mapper: FunctionMapper,
+    /// all functions in the order they are shown
+    pub midi_functions: Vec<String>,
+    pub function_states: HashMap<String, FunctionState>,
+    pub last_midi_msg: Option<Box<dyn MidiMsgBase>>,
+    pub connection_manager: Option<JackConnectionManager>,
+    pub show_connections: bool,
+}
+
+impl FunctionMonitorGui {
+    /// Dot lighting up when the function is called and fading out
+    fn flash_ui(ui: &mut egui::Ui, last_call: Option<Instant>) {
+        let size = ui.spacing().interact_size.y * egui::vec2(0.6, 1.0);
+        let (rect, _response) = ui.allocate_exact_size(size, Sense::hover());
+        let elapsed = last_call.map_or(FLASH_DURATION, |last_call| last_call.elapsed());
+        let how_on = 1.0 - elapsed.as_secs_f32() / FLASH_DURATION.as_secs_f32();
+        let visuals = ui.visuals();
+        let color = if how_on > 0.0 {
+            Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * how_on)
+        } else {
+            visuals.extreme_bg_color
+        };
+        ui.painter()
+            .circle_filled(rect.center(), rect.width() / 2.0, color);
+    }
+
+    fn function_ui(ui: &mut egui::Ui, midi_function: &str, function_state: Option<&FunctionState>) {
+        let Some(function_state) = function_state else {
+            Self::flash_ui(ui, None);
+            ui.add(midi_id_value_indicator(midi_function, 0));
+            ui.label("-");
+            ui.label("-");
+            ui.end_row();
+            return;
+        };
+        Self::flash_ui(ui, function_state.last_call);
+        match function_state.value {
+            Some(FunctionValue::Note(on, velocity)) => {
+                ui.add(midi_note_status_intensity_indicator(
+                    midi_function,
+                    &on,
+                    velocity as u32,
+                ));
+            }
+            Some(FunctionValue::Value(value)) => {
+                ui.add(midi_id_value_indicator(midi_function, value as u32));
+            }
+            Some(FunctionValue::Value14Bit(value)) => {
+                ui.add(midi_id_double_precision_value_indicator(
+                    midi_function,
+                    value as u32,
+                ));
+            }
+            Some(FunctionValue::Delta(_)) | None => {
+                ui.add(midi_id_value_indicator(
+                    midi_function,
+                    function_state.relative_value,
+                ));
+            }
+        }
+        ui.label(
+            function_state
+                .value
+                .map_or("-".to_string(), |value| value.to_string()),
+        );
+        ui.label(
+            function_state
+                .source
+                .as_ref()
+                .map_or("-".to_string(), |source| source.to_string()),
+        )
+        .on_hover_text(format!("called {} times", function_state.num_calls));
+        ui.end_row();
+    }
+}
+
+impl eframe::App for FunctionMonitorGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(ref midi_receiver) = self.midi_receiver {
+            while let Ok(current_midi_msg) = midi_receiver.try_recv() {
+                let midi_advanced_msg = MidiMsgAdvanced::from_current_and_last_opt_midi_msgs((
+                    current_midi_msg,
+                    &mut self.last_midi_msg,
+                ));
+                let Some(midi_advanced_msg) = midi_advanced_msg else {
+                    continue;
+                };
+                for function_call in self.function_mapper.map(&midi_advanced_msg) {
+                    self.function_states
+                        .entry(function_call.function)
+                        .or_default()
+                        .call(function_call.value, function_call.source);
+                }
+            }
+        }
+        egui::TopBottomPanel::top("control").show(ctx, |ui| {
+            ui.heading("FunctionMonitor");
+            ui.horizontal(|ui| {
+                if ui.button("close").clicked() {
+                    if let Some(x) = &self.tx_close {
+                        x.send(false).unwrap();
+                        if let Some(midi_thread) = self.midi_thread.take() {
+                            let _ = midi_thread.join();
+                        }
+                    };
+                    ctx.send_viewport_cmd(ViewportCommand::Close)
+                }
+                if self.connection_manager.is_some() {
+                    ui.toggle_value(&mut self.show_connections, "connections");
+                }
+            });
+        });
+        if let Some(ref connection_manager) = self.connection_manager {
+            egui::Window::new("jack connections")
+                .open(&mut self.show_connections)
+                .show(ctx, |ui| jack_connection_panel(ui, connection_manager));
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("functions").striped(true).show(ui, |ui| {
+                    for midi_function in self.midi_functions.iter() {
+                        Self::function_ui(
+                            ui,
+                            midi_function,
+                            self.function_states.get(midi_function),
+                        );
+                    }
+                });
+            });
+        });
+        ctx.request_repaint();
+    }
+}
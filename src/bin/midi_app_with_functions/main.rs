@@ -16,33 +16,53 @@
  */
 
 use crossbeam_channel::unbounded;
-use std::{collections::HashMap, sync::mpsc};
+use std::sync::mpsc;
 
 use clap::Parser;
+use eframe::egui::ViewportBuilder;
 use midi_mapper::{
-    jackmidi::{MidiMsgAdvanced, MidiMsgBase},
-    jackprocess::start_jack_thread,
-    midi_function::{
-        parse_json_file_to_midi_functions,
-        parse_json_file_to_midi_functions_with_midi_msgs_advanced, MidiFunction, MidiFunctionFile,
-    },
+    jackmidi::MidiMsgBase,
+    jackprocess::{start_jack_thread_with_ports, AutoConnect, JackConnectionManager},
+    midi_capture::parse_capture_file,
+    midi_function::{parse_file_to_midi_functions, MidiFunction, MidiFunctionFile},
+    midi_mapping::{parse_file_to_midi_mapping, MidiMappingFile},
+    midi_player::start_midi_player_thread,
 };
+mod function_monitor;
 mod midi_app_with_functions;
-use midi_app_with_functions::basic_loop;
+use function_monitor::FunctionMonitorGui;
+use midi_app_with_functions::{basic_loop, print_binding_conflicts, FunctionMapper};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// midi_functions_filepath
-    #[arg(short, long, value_name = "filepath")]
+    #[arg(short = 'f', long, value_name = "filepath")]
     pub midi_functions_filepath: Option<String>,
     /// midi_mapping_filepath
     #[arg(short, long, value_name = "filepath")]
     pub midi_mapping_filepath: Option<String>,
+    /// play a .mid file or json capture instead of reading from jack
+    #[arg(short, long, value_name = "capture_filepath")]
+    pub play: Option<String>,
+    /// play the capture as fast as possible instead of in real time
+    #[arg(long)]
+    pub fast: bool,
+    /// name of an input port, give it multiple times for multiple ports (default: midi_in)
+    #[arg(short, long = "input_port", value_name = "name")]
+    pub input_ports: Vec<String>,
+    /// connect to jack midi outputs matching the regex, `input_port=regex` connects a single
+    /// input port, can be given multiple times
+    #[arg(short, long = "connect", value_name = "[input_port=]regex")]
+    pub connects: Vec<AutoConnect>,
+    /// show the functions with their current values in a window instead of printing the calls
+    #[arg(short, long)]
+    pub gui: bool,
 }
 
 fn main() {
-    let midi_functions = Args::parse().midi_functions_filepath.map_or_else(
+    let args = Args::parse();
+    let midi_functions = args.midi_functions_filepath.map_or_else(
         || MidiFunctionFile {
             midi_functions: vec![
                 MidiFunction::new("Volume".to_string()),
@@ -51,37 +71,84 @@ fn main() {
             ],
         },
         |filepath| {
-            parse_json_file_to_midi_functions(&filepath).map_or_else(
-                |err| {
-                    println!("{err}");
-                    MidiFunctionFile::default()
-                },
-                |file_path| file_path,
-            )
+            parse_file_to_midi_functions(&filepath).unwrap_or_else(|err| {
+                println!("{err}");
+                MidiFunctionFile::default()
+            })
         },
     );
     println!("midi_function: {:?}", midi_functions);
-    let midi_functions_with_midi_advanced_msgs: Result<
-        HashMap<String, Vec<MidiMsgAdvanced>>,
-        String,
-    > = Args::parse().midi_mapping_filepath.map_or_else(
-        || Ok(HashMap::<String, Vec<MidiMsgAdvanced>>::new()),
-        |filepath| {
-            // Todo: parse filepath
-            parse_json_file_to_midi_functions_with_midi_msgs_advanced(&filepath)
-        },
+    let midi_mapping: Result<MidiMappingFile, String> = args.midi_mapping_filepath.map_or_else(
+        || Ok(MidiMappingFile::default()),
+        |filepath| parse_file_to_midi_mapping(&filepath),
     );
-    let (midi_sender, midi_receiver): (
-        std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
-        std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>,
-    ) = mpsc::sync_channel(64);
+    let (midi_sender, midi_receiver) = mpsc::sync_channel::<Box<dyn MidiMsgBase>>(64);
     let (tx_close, rx_close) = unbounded();
+    let mut connection_manager = None;
 
-    let jack_midi_thread = start_jack_thread(rx_close, midi_sender,"midi_app".to_string());
+    let jack_midi_thread = match args.play {
+        Some(capture_filepath) => {
+            let midi_capture_events = parse_capture_file(&capture_filepath).unwrap_or_else(|err| {
+                println!("{err}");
+                std::process::exit(1);
+            });
+            start_midi_player_thread(rx_close, midi_sender, midi_capture_events, !args.fast)
+        }
+        None => {
+            // the connections can only be changed in the window
+            let jack_graph_updater = args.gui.then(|| {
+                let (jack_connection_manager, jack_graph_updater) = JackConnectionManager::new();
+                connection_manager = Some(jack_connection_manager);
+                jack_graph_updater
+            });
+            start_jack_thread_with_ports(
+                rx_close,
+                midi_sender,
+                "midi_app".to_string(),
+                args.input_ports,
+                args.connects,
+                jack_graph_updater,
+            )
+        }
+    };
 
-    println!("midi_mapping: {:?}", midi_functions_with_midi_advanced_msgs);
+    println!("midi_mapping: {:?}", midi_mapping);
+    if args.gui {
+        let midi_mapping = midi_mapping.unwrap();
+        print_binding_conflicts(&midi_mapping);
+        // the functions of the functions file first, then the ones only found in the mapping
+        let mut function_names: Vec<String> = midi_functions
+            .midi_functions
+            .iter()
+            .map(|midi_function| midi_function.get_name())
+            .collect();
+        for name in midi_mapping.midi_functions.keys() {
+            if !function_names.contains(name) {
+                function_names.push(name.clone());
+            }
+        }
+        let function_monitor_gui = FunctionMonitorGui {
+            midi_receiver: Some(midi_receiver),
+            midi_thread: Some(jack_midi_thread),
+            tx_close: Some(tx_close),
+            function_mapper: FunctionMapper::new(&midi_mapping),
+            midi_functions: function_names,
+            connection_manager,
+            ..Default::default()
+        };
+        let options = eframe::NativeOptions {
+            viewport: ViewportBuilder::default().with_inner_size([600.0, 600.0]),
+            ..Default::default()
+        };
+        let _ = eframe::run_native(
+            "FunctionMonitor",
+            options,
+            Box::new(|_cc| Box::new(function_monitor_gui)),
+        );
+        return;
+    }
     basic_loop(
-        midi_functions_with_midi_advanced_msgs.unwrap(),
+        midi_mapping.unwrap(),
         Some(jack_midi_thread),
         Some(tx_close),
         Some(midi_receiver),
@@ -1,54 +1,151 @@
+use midi_mapper::binding_conflicts::find_binding_conflicts;
+use midi_mapper::control_address::ControlAddress;
 use midi_mapper::jackmidi::{MidiMsgAdvanced, MidiMsgBase};
+use midi_mapper::midi_mapping::{BindingMode, MidiBinding, MidiMappingFile};
 use std::collections::HashMap;
+use std::fmt;
+
+/// Value a function is called with, relative bindings send the signed step
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FunctionValue {
+    Note(bool, u8),
+    Value(u16),
+    Value14Bit(u16),
+    Delta(i8),
+}
+
+impl fmt::Display for FunctionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionValue::Note(on, velocity) => {
+                write!(f, "{} {}", if *on { "on" } else { "off" }, velocity)
+            }
+            FunctionValue::Value(value) | FunctionValue::Value14Bit(value) => {
+                write!(f, "{}", value)
+            }
+            FunctionValue::Delta(delta) => write!(f, "{:+}", delta),
+        }
+    }
+}
+
+/// A function called by a midi message and the control which sent it
+#[derive(Clone, Debug)]
+pub struct FunctionCall {
+    pub function: String,
+    pub value: FunctionValue,
+    pub source: ControlAddress,
+}
+
+/// Looks up the functions bound to a midi message
+#[derive(Default)]
+pub struct FunctionMapper {
+    // reverse map of the mapping, the mode is kept with the function
+    midi_bindings2midi_functions: HashMap<MidiBinding, Vec<(String, Option<BindingMode>)>>,
+}
+
+impl FunctionMapper {
+    pub fn new(midi_mapping: &MidiMappingFile) -> Self {
+        let mut midi_bindings2midi_functions: HashMap<
+            MidiBinding,
+            Vec<(String, Option<BindingMode>)>,
+        > = HashMap::new();
+        for (key, bindings) in midi_mapping.midi_functions.iter() {
+            for binding in bindings {
+                midi_bindings2midi_functions
+                    .entry(binding.without_mode())
+                    .or_default()
+                    .push((key.clone(), binding.mode));
+            }
+        }
+        FunctionMapper {
+            midi_bindings2midi_functions,
+        }
+    }
+
+    pub fn map(&self, midi_advanced_msg: &MidiMsgAdvanced) -> Vec<FunctionCall> {
+        let Some(binding) = MidiBinding::from_midi_msg_advanced(midi_advanced_msg) else {
+            return Vec::new();
+        };
+        let Some(source) = midi_advanced_msg.get_address() else {
+            return Vec::new();
+        };
+        // bindings without port listen to all ports
+        let functions = self.midi_bindings2midi_functions.get(&binding).or_else(|| {
+            self.midi_bindings2midi_functions.get(&MidiBinding {
+                port: None,
+                ..binding.clone()
+            })
+        });
+        let Some(functions) = functions else {
+            return Vec::new();
+        };
+        functions
+            .iter()
+            .filter_map(|(function, mode)| {
+                let value = match midi_advanced_msg {
+                    MidiMsgAdvanced::MidiEmpty => return None,
+                    MidiMsgAdvanced::MidiNoteOnOff(_, on, velocity) => {
+                        FunctionValue::Note(*on, *velocity)
+                    }
+                    MidiMsgAdvanced::MidiControlIdValue(_, value) => {
+                        match mode.and_then(|mode| mode.relative_delta(*value as u8)) {
+                            Some(delta) => FunctionValue::Delta(delta),
+                            None => FunctionValue::Value(*value),
+                        }
+                    }
+                    MidiMsgAdvanced::MidiControl2IdsValue(_, _, value) => {
+                        FunctionValue::Value14Bit(*value)
+                    }
+                };
+                Some(FunctionCall {
+                    function: function.clone(),
+                    value,
+                    source: source.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Prints the overlapping bindings, all of their functions are called
+pub fn print_binding_conflicts(midi_mapping: &MidiMappingFile) {
+    for conflict in find_binding_conflicts(&midi_mapping.midi_functions) {
+        println!("warning: {}", conflict);
+    }
+}
 
 pub fn basic_loop(
-    function_msgs_map: HashMap<String, Vec<MidiMsgAdvanced>>,
+    midi_mapping: MidiMappingFile,
     _midi_thread: Option<std::thread::JoinHandle<()>>,
     _tx_close: Option<crossbeam_channel::Sender<bool>>,
     midi_receiver: Option<std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>>,
 ) {
-    // creat a reverse Hashmap
-    let mut midi_advanced_msgs2midi_functions: HashMap<MidiMsgAdvanced, Vec<String>> =
-        HashMap::new();
-    for (key, value_vec) in function_msgs_map {
-        let key_insert = key.clone();
-        for value in value_vec {
-            if let Some(ref mut midi_function_vec) =
-                midi_advanced_msgs2midi_functions.get_mut(&value)
-            {
-                midi_function_vec.push(key_insert.clone());
-            } else {
-                midi_advanced_msgs2midi_functions.insert(value, vec![key_insert.clone()]);
-            }
-        }
-    }
+    print_binding_conflicts(&midi_mapping);
+    let function_mapper = FunctionMapper::new(&midi_mapping);
     let mut last_midi_msg: Option<Box<dyn MidiMsgBase>> = None;
-    let mut _run_loop = true;
-    while _run_loop {
-        let mut received_midi_advanced_messages: Vec<MidiMsgAdvanced> = Vec::new();
-        // check buffer for new message
-        if let Some(ref midi_receiver) = midi_receiver {
-            while let Ok(current_midi_msg) = midi_receiver.try_recv() {
-                let midi_advanced_msg = MidiMsgAdvanced::from_current_and_last_opt_midi_msgs((
-                    current_midi_msg,
-                    &mut last_midi_msg,
-                ));
-                if let Some(midi_advanced_msg) = midi_advanced_msg {
-                    let _id = midi_advanced_msg.get_id();
-                    received_midi_advanced_messages.push(midi_advanced_msg);
-                }
-            }
-        }
-        for midi_advanced_msg in received_midi_advanced_messages {
-            if let Some(functions) = midi_advanced_msgs2midi_functions.get(&midi_advanced_msg) {
-                for function in functions {
-                    println!(
-                        "function: {} called with msgs: {}",
-                        function, midi_advanced_msg
-                    );
-                }
+    let Some(midi_receiver) = midi_receiver else {
+        return;
+    };
+    // blocks until a message arrives, returns when the midi thread or player is gone
+    while let Ok(current_midi_msg) = midi_receiver.recv() {
+        let midi_advanced_msg = MidiMsgAdvanced::from_current_and_last_opt_midi_msgs((
+            current_midi_msg,
+            &mut last_midi_msg,
+        ));
+        let Some(midi_advanced_msg) = midi_advanced_msg else {
+            continue;
+        };
+        for function_call in function_mapper.map(&midi_advanced_msg) {
+            match function_call.value {
+                FunctionValue::Delta(delta) => println!(
+                    "function: {} called with delta: {}",
+                    function_call.function, delta
+                ),
+                _ => println!(
+                    "function: {} called with msgs: {}",
+                    function_call.function, midi_advanced_msg
+                ),
             }
         }
-        // compare
     }
 }
@@ -15,26 +15,62 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use clap::Parser;
 use crossbeam_channel::unbounded;
 use eframe::{self, egui::ViewportBuilder};
-use midi_mapper::{jackmidi::MidiMsgBase, jackprocess::start_jack_thread};
+use midi_mapper::{
+    controller_profile::{parse_file_to_controller_profile, ControllerProfile},
+    jackmidi::MidiMsgBase,
+    jackprocess::{start_jack_thread_with_ports, AutoConnect, JackConnectionManager},
+};
 use std::sync::mpsc;
 mod midi_debugger_gui;
 use midi_debugger_gui::MidiDebuggerGui;
 
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// name of an input port, give it multiple times for multiple ports (default: midi_in)
+    #[arg(short, long = "input_port", value_name = "name")]
+    pub input_ports: Vec<String>,
+    /// connect to jack midi outputs matching the regex, `input_port=regex` connects a single
+    /// input port, can be given multiple times
+    #[arg(short, long = "connect", value_name = "[input_port=]regex")]
+    pub connects: Vec<AutoConnect>,
+    /// controller profile with the names of the controls, can be given multiple times
+    #[arg(long = "profile", value_name = "filepath")]
+    pub profiles: Vec<String>,
+}
+
 fn main() {
-    let (midi_sender, midi_receiver): (
-        std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
-        std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>,
-    ) = mpsc::sync_channel(64);
+    let args = Args::parse();
+    let controller_profiles: Vec<ControllerProfile> = args
+        .profiles
+        .iter()
+        .filter_map(|filepath| {
+            parse_file_to_controller_profile(filepath)
+                .map_err(|err| println!("{err}"))
+                .ok()
+        })
+        .collect();
+    let (midi_sender, midi_receiver) = mpsc::sync_channel::<Box<dyn MidiMsgBase>>(64);
     let (tx_close, rx_close) = unbounded();
-    let jack_midi_thread = start_jack_thread(rx_close, midi_sender,"midi_debugger".to_string());
+    let (connection_manager, jack_graph_updater) = JackConnectionManager::new();
+    let jack_midi_thread = start_jack_thread_with_ports(
+        rx_close,
+        midi_sender,
+        "midi_debugger".to_string(),
+        args.input_ports,
+        args.connects,
+        Some(jack_graph_updater),
+    );
     let midi_debugger_gui = MidiDebuggerGui {
         midi_receiver: Some(midi_receiver),
         midi_thread: Some(jack_midi_thread),
         tx_close: Some(tx_close),
-        n_items: 0,
-        midi_msgs: Vec::new(),
+        connection_manager: Some(connection_manager),
+        controller_profiles,
+        ..Default::default()
     };
 
     let options = eframe::NativeOptions {
@@ -15,71 +15,394 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use eframe::egui::{self, ScrollArea, ViewportCommand};
-use midi_mapper::jackmidi::MidiMsgBase;
+use eframe::egui::{self, Color32, RichText, ScrollArea, ViewportCommand};
+use midi_mapper::{
+    controller_profile::{find_profile_control, ControllerProfile},
+    jack_connection_panel::jack_connection_panel,
+    jackmidi::MidiMsgBase,
+    jackprocess::JackConnectionManager,
+    midi_capture::MidiRecorder,
+    midi_capture_export::{midi_capture_records, write_capture_records},
+    midi_decode::{format_duration_us, DecodedMsg, MidiDecoder},
+    midi_filter::MidiFilter,
+    midi_history::{HistoryEntry, MidiHistory},
+    smf::write_smf_type0,
+};
+use std::collections::VecDeque;
 
+/// Which columns the message list shows
+pub struct DebuggerColumns {
+    pub time: bool,
+    pub port: bool,
+    /// name of the control in the controller profiles
+    pub name: bool,
+    pub delta: bool,
+    pub control_delta: bool,
+    pub hex: bool,
+    pub decoded: bool,
+    pub value_14bit: bool,
+    pub raw: bool,
+}
+
+impl Default for DebuggerColumns {
+    fn default() -> Self {
+        DebuggerColumns {
+            time: false,
+            port: true,
+            name: true,
+            delta: true,
+            control_delta: true,
+            hex: true,
+            decoded: true,
+            value_14bit: true,
+            raw: false,
+        }
+    }
+}
+
+impl DebuggerColumns {
+    fn format_row(
+        &self,
+        midi_msg: &dyn MidiMsgBase,
+        decoded_msg: &DecodedMsg,
+        controller_profiles: &[ControllerProfile],
+    ) -> String {
+        let optional_duration = |duration: Option<u64>| match duration {
+            Some(duration) => format!("+{}", format_duration_us(duration)),
+            None => "-".to_string(),
+        };
+        let mut columns = Vec::new();
+        if self.time {
+            columns.push(format!("{:>14}", decoded_msg.time));
+        }
+        if self.port {
+            columns.push(format!(
+                "{:<10}",
+                decoded_msg.port.as_deref().unwrap_or("-")
+            ));
+        }
+        if self.name {
+            let name = midi_msg.get_address().and_then(|address| {
+                find_profile_control(controller_profiles, &address)
+                    .map(|control| control.name.clone())
+            });
+            columns.push(format!("{:<16}", name.as_deref().unwrap_or("-")));
+        }
+        if self.delta {
+            columns.push(format!("{:>14}", optional_duration(decoded_msg.delta)));
+        }
+        if self.control_delta {
+            columns.push(format!(
+                "{:>14}",
+                optional_duration(decoded_msg.control_delta)
+            ));
+        }
+        if self.hex {
+            columns.push(format!("{:<9}", decoded_msg.hex()));
+        }
+        if self.decoded {
+            columns.push(format!("{:<40}", decoded_msg.description));
+        }
+        if self.value_14bit {
+            columns.push(match decoded_msg.value_14bit {
+                Some(value_14bit) => format!("{:>5}", value_14bit),
+                None => format!("{:>5}", ""),
+            });
+        }
+        if self.raw {
+            columns.push(midi_msg.to_string());
+        }
+        columns.join(" | ")
+    }
+}
+
+#[derive(Default)]
 pub struct MidiDebuggerGui {
     pub midi_receiver: Option<std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>>,
     pub midi_thread: Option<std::thread::JoinHandle<()>>,
     pub tx_close: Option<crossbeam_channel::Sender<bool>>,
     pub n_items: usize,
-    pub midi_msgs: Vec<Box<dyn MidiMsgBase>>,
+    pub midi_msgs: MidiHistory,
+    pub midi_recorder: MidiRecorder,
+    pub smf_path: String,
+    pub status_text: String,
+    pub midi_filter: MidiFilter,
+    // ids of midi_msgs shown with applied_midi_filter
+    pub filtered_rows: VecDeque<usize>,
+    pub applied_midi_filter: MidiFilter,
+    pub marker_text: String,
+    pub midi_decoder: MidiDecoder,
+    pub columns: DebuggerColumns,
+    pub list_export_path: String,
+    pub connection_manager: Option<JackConnectionManager>,
+    pub show_connections: bool,
+    pub controller_profiles: Vec<ControllerProfile>,
 }
 
-impl Default for MidiDebuggerGui {
-    fn default() -> Self {
-        Self {
-            midi_receiver: None,
-            midi_thread: None,
-            tx_close: None,
-            n_items: 0,
-            midi_msgs: Vec::new(),
+impl MidiDebuggerGui {
+    fn filter_ui(&mut self, ui: &mut egui::Ui) {
+        let midi_filter = &mut self.midi_filter;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("channels:");
+            for channel in 0..16 {
+                let mut shown = midi_filter.is_channel_shown(channel);
+                if ui
+                    .checkbox(&mut shown, format!("{}", channel + 1))
+                    .changed()
+                {
+                    midi_filter.set_channel_shown(channel, shown);
+                }
+            }
+            if ui.button("all").clicked() {
+                midi_filter.channel_mask = 0xffff;
+            }
+            if ui.button("none").clicked() {
+                midi_filter.channel_mask = 0;
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.checkbox(&mut midi_filter.show_note, "note");
+            ui.checkbox(&mut midi_filter.show_control_change, "CC");
+            ui.checkbox(&mut midi_filter.show_pitch_bend, "pitch bend");
+            ui.checkbox(&mut midi_filter.show_sysex, "SysEx");
+            ui.checkbox(&mut midi_filter.show_realtime, "real-time");
+            ui.checkbox(&mut midi_filter.show_other, "other");
+            ui.checkbox(
+                &mut midi_filter.hide_clock_and_active_sensing,
+                "hide clock and active sensing",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("notes:");
+            ui.add(egui::DragValue::new(&mut midi_filter.note_min).clamp_range(0..=127));
+            ui.add(egui::DragValue::new(&mut midi_filter.note_max).clamp_range(0..=127));
+            ui.label("controllers:");
+            ui.add(egui::DragValue::new(&mut midi_filter.controller_min).clamp_range(0..=127));
+            ui.add(egui::DragValue::new(&mut midi_filter.controller_max).clamp_range(0..=127));
+        });
+        ui.horizontal(|ui| {
+            ui.label("search:");
+            ui.text_edit_singleline(&mut midi_filter.search_text);
+            if ui.button("reset").clicked() {
+                *midi_filter = MidiFilter::default();
+            }
+        });
+    }
+
+    /// Keep filtered_rows in sync with the filter and the received messages
+    fn update_filtered_rows(&mut self, first_new_id: usize) {
+        let first_id = if self.applied_midi_filter != self.midi_filter {
+            self.applied_midi_filter = self.midi_filter.clone();
+            self.filtered_rows.clear();
+            self.midi_msgs.first_id()
+        } else {
+            first_new_id.max(self.midi_msgs.first_id())
+        };
+        for id in first_id..self.midi_msgs.end_id() {
+            let shown = match self.midi_msgs.get(id) {
+                Some(HistoryEntry::Msg(midi_msg, _)) => self.midi_filter.matches(midi_msg.as_ref()),
+                Some(HistoryEntry::Marker { .. }) => true,
+                None => false,
+            };
+            if shown {
+                self.filtered_rows.push_back(id);
+            }
         }
+        // entries dropped by the ring buffer
+        while self
+            .filtered_rows
+            .front()
+            .is_some_and(|id| *id < self.midi_msgs.first_id())
+        {
+            self.filtered_rows.pop_front();
+        }
+    }
+
+    fn columns_ui(&mut self, ui: &mut egui::Ui) {
+        let columns = &mut self.columns;
+        ui.horizontal_wrapped(|ui| {
+            ui.checkbox(&mut columns.time, "time");
+            ui.checkbox(&mut columns.port, "port");
+            ui.checkbox(&mut columns.name, "name");
+            ui.checkbox(&mut columns.delta, "delta");
+            ui.checkbox(&mut columns.control_delta, "delta same control");
+            ui.checkbox(&mut columns.hex, "hex");
+            ui.checkbox(&mut columns.decoded, "decoded");
+            ui.checkbox(&mut columns.value_14bit, "14 bit value");
+            ui.checkbox(&mut columns.raw, "raw");
+        });
+    }
+
+    fn history_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.midi_msgs.is_paused() {
+                if ui.button("resume").clicked() {
+                    self.midi_msgs.resume();
+                }
+            } else if ui.button("pause").clicked() {
+                self.midi_msgs.pause();
+            }
+            ui.add(egui::TextEdit::singleline(&mut self.marker_text).hint_text("marker"));
+            if ui.button("add marker").clicked() {
+                let text = if self.marker_text.is_empty() {
+                    "marker".to_string()
+                } else {
+                    std::mem::take(&mut self.marker_text)
+                };
+                self.midi_msgs.push_marker(jack::get_time(), text);
+            }
+            ui.label("keep last:");
+            let mut max_len = self.midi_msgs.max_len();
+            if ui
+                .add(egui::DragValue::new(&mut max_len).clamp_range(100..=1_000_000))
+                .changed()
+            {
+                self.midi_msgs.set_max_len(max_len);
+            }
+        });
     }
 }
 
 impl eframe::App for MidiDebuggerGui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // before the ui, resuming adds the held back entries
+        let first_new_id = self.midi_msgs.end_id();
         let mut received_midi_msgs = Vec::new();
         if let Some(ref midi_receiver) = self.midi_receiver {
             while let Ok(m) = midi_receiver.try_recv() {
                 received_midi_msgs.push(m);
             }
         }
+        if let Some(ref connection_manager) = self.connection_manager {
+            egui::Window::new("jack connections")
+                .open(&mut self.show_connections)
+                .show(ctx, |ui| jack_connection_panel(ui, connection_manager));
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             let window_rect = ctx.screen_rect();
             let window_width = window_rect.width();
-            let window_height = window_rect.height();
             ui.heading("MidiDebugger");
             ui.vertical(|ui| {
                 if ui.button("close").clicked() {
                     if let Some(x) = &self.tx_close {
                         x.send(false).unwrap();
+                        if let Some(midi_thread) = self.midi_thread.take() {
+                            let _ = midi_thread.join();
+                        }
                         ctx.send_viewport_cmd(ViewportCommand::Close)
                     };
                 }
 
+                if self.connection_manager.is_some() {
+                    ui.toggle_value(&mut self.show_connections, "connections");
+                }
+
                 if ui.button("clear").clicked() {
-                    // TODO clear
-                    self.n_items = 0;
                     self.midi_msgs.clear();
+                    self.filtered_rows.clear();
+                    self.midi_decoder.reset();
+                }
+
+                ui.horizontal(|ui| {
+                    if self.midi_recorder.is_recording() {
+                        if ui.button("stop").clicked() {
+                            self.midi_recorder.stop();
+                        }
+                    } else if ui.button("record").clicked() {
+                        self.midi_recorder.start();
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut self.smf_path).hint_text("capture.mid"));
+                    if ui.button("export as .mid").clicked() {
+                        self.status_text =
+                            match write_smf_type0(&self.smf_path, self.midi_recorder.events()) {
+                                Ok(()) => format!(
+                                    "{} messages written to {}",
+                                    self.midi_recorder.events().len(),
+                                    self.smf_path
+                                ),
+                                Err(err) => err,
+                            };
+                    }
+                    if self.midi_recorder.is_recording() {
+                        ui.label(format!(
+                            "recording: {} messages",
+                            self.midi_recorder.events().len()
+                        ));
+                    } else {
+                        ui.label(&self.status_text);
+                    }
+                });
+
+                for midi_msg in received_midi_msgs.iter() {
+                    self.midi_recorder.record(midi_msg.as_ref());
                 }
 
-                self.midi_msgs.append(&mut received_midi_msgs);
-                self.n_items = self.midi_msgs.len();
+                self.history_ui(ui);
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.list_export_path)
+                            .hint_text("capture.csv or capture.jsonl"),
+                    );
+                    if ui.button("export list").clicked() {
+                        let records = midi_capture_records(&self.midi_msgs);
+                        self.status_text =
+                            match write_capture_records(&self.list_export_path, &records) {
+                                Ok(()) => format!(
+                                    "{} entries written to {}",
+                                    records.len(),
+                                    self.list_export_path
+                                ),
+                                Err(err) => err,
+                            };
+                    }
+                });
+                egui::CollapsingHeader::new("filter").show(ui, |ui| self.filter_ui(ui));
+                egui::CollapsingHeader::new("columns").show(ui, |ui| self.columns_ui(ui));
+
+                for midi_msg in received_midi_msgs {
+                    let decoded_msg = self.midi_decoder.decode(midi_msg.as_ref());
+                    self.midi_msgs
+                        .push(HistoryEntry::Msg(midi_msg, decoded_msg));
+                }
+                self.update_filtered_rows(first_new_id);
+                self.n_items = self.filtered_rows.len();
+                ui.label(format!(
+                    "showing {} of {} entries{}",
+                    self.n_items,
+                    self.midi_msgs.len(),
+                    if self.midi_msgs.is_paused() {
+                        format!(", paused, {} new", self.midi_msgs.paused_len())
+                    } else {
+                        String::new()
+                    }
+                ));
                 let text_style = egui::TextStyle::Body;
                 let row_height = ui.text_style_height(&text_style);
                 if self.n_items > 0 {
                     ScrollArea::vertical()
-                        .stick_to_bottom(true)
-                        .min_scrolled_height(window_height - 80.0)
-                        .max_height(window_height - 80.0)
+                        .stick_to_bottom(!self.midi_msgs.is_paused())
+                        .min_scrolled_height(ui.available_height())
+                        .max_height(ui.available_height())
                         .min_scrolled_width(window_width - 60.0)
                         .max_width(window_width - 60.0)
                         .show_rows(ui, row_height, self.n_items, |ui, row_range| {
                             for row in row_range {
-                                let text = format!("{}", self.midi_msgs[row]);
-                                ui.label(text);
+                                match self.midi_msgs.get(self.filtered_rows[row]) {
+                                    Some(entry @ HistoryEntry::Marker { .. }) => {
+                                        ui.colored_label(Color32::YELLOW, entry.to_string());
+                                    }
+                                    Some(HistoryEntry::Msg(midi_msg, decoded_msg)) => {
+                                        ui.label(
+                                            RichText::new(self.columns.format_row(
+                                                midi_msg.as_ref(),
+                                                decoded_msg,
+                                                &self.controller_profiles,
+                                            ))
+                                            .monospace(),
+                                        );
+                                    }
+                                    None => {}
+                                }
                             }
                         });
                 }
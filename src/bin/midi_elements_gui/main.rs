@@ -19,14 +19,19 @@ use clap::Parser;
 use crossbeam_channel::unbounded;
 use eframe::{self, egui::ViewportBuilder};
 use midi_mapper::{
-    jackmidi::{MidiMsgAdvanced, MidiMsgBase},
-    jackprocess::start_jack_thread,
-    midi_function::{parse_json_file_to_midi_functions, MidiFunction, MidiFunctionFile},
+    controller_profile::{parse_file_to_controller_profile, ControllerProfile},
+    jackmidi::MidiMsgBase,
+    jackprocess::{start_jack_thread_with_ports, AutoConnect, JackConnectionManager},
+    midi_capture::parse_capture_file,
+    midi_function::{parse_file_to_midi_functions, MidiFunction, MidiFunctionFile},
+    midi_mapping::{parse_file_to_midi_mapping, MidiBinding, MidiMappingFile},
+    midi_player::start_midi_player_thread,
 };
 use std::{
     collections::{HashMap, HashSet},
     sync::mpsc,
 };
+mod mapping_edit;
 mod midi_elements_gui;
 use midi_elements_gui::MidiElementsGui;
 
@@ -36,16 +41,54 @@ struct Args {
     /// filepath
     #[arg(short, long, value_name = "filepath")]
     pub filepath: Option<String>,
+    /// mapping file to edit, its functions are added to the function list
+    #[arg(short, long, value_name = "mapping_filepath")]
+    pub mapping: Option<String>,
+    /// play a .mid file or json capture instead of reading from jack
+    #[arg(short, long, value_name = "capture_filepath")]
+    pub play: Option<String>,
+    /// play the capture as fast as possible instead of in real time
+    #[arg(long)]
+    pub fast: bool,
+    /// name of an input port, give it multiple times for multiple ports (default: midi_in)
+    #[arg(short, long = "input_port", value_name = "name")]
+    pub input_ports: Vec<String>,
+    /// connect to jack midi outputs matching the regex, `input_port=regex` connects a single
+    /// input port, can be given multiple times
+    #[arg(short, long = "connect", value_name = "[input_port=]regex")]
+    pub connects: Vec<AutoConnect>,
+    /// controller profile with the names of the controls, can be given multiple times
+    #[arg(long = "profile", value_name = "filepath")]
+    pub profiles: Vec<String>,
 }
 
 fn main() {
-    let (midi_sender, midi_receiver): (
-        std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
-        std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>,
-    ) = mpsc::sync_channel(64);
+    let (midi_sender, midi_receiver) = mpsc::sync_channel::<Box<dyn MidiMsgBase>>(64);
     let (tx_close, rx_close) = unbounded();
-    let jack_midi_thread = start_jack_thread(rx_close, midi_sender, "midi_elements".to_string());
-    let midi_functions = Args::parse().filepath.map_or_else(
+    let args = Args::parse();
+    let mut connection_manager = None;
+    let jack_midi_thread = match args.play {
+        Some(capture_filepath) => {
+            let midi_capture_events = parse_capture_file(&capture_filepath).unwrap_or_else(|err| {
+                println!("{err}");
+                std::process::exit(1);
+            });
+            start_midi_player_thread(rx_close, midi_sender, midi_capture_events, !args.fast)
+        }
+        None => {
+            let (jack_connection_manager, jack_graph_updater) = JackConnectionManager::new();
+            connection_manager = Some(jack_connection_manager);
+            start_jack_thread_with_ports(
+                rx_close,
+                midi_sender,
+                "midi_elements".to_string(),
+                args.input_ports,
+                args.connects,
+                Some(jack_graph_updater),
+            )
+        }
+    };
+    let mut midi_functions = args.filepath.as_ref().map_or_else(
         || MidiFunctionFile {
             midi_functions: vec![
                 MidiFunction::new("Volume".to_string()),
@@ -54,33 +97,72 @@ fn main() {
             ],
         },
         |filepath| {
-            parse_json_file_to_midi_functions(&filepath).map_or_else(
-                |err| {
-                    println!("{err}");
-                    MidiFunctionFile::default()
-                },
-                |file_path| file_path,
-            )
+            parse_file_to_midi_functions(filepath).unwrap_or_else(|err| {
+                println!("{err}");
+                MidiFunctionFile::default()
+            })
         },
     );
-    let mut midi_functions_set = HashSet::new();
-    let mut midi_functions_with_elements_ids: HashMap<String, Vec<MidiMsgAdvanced>> =
-        HashMap::new();
-    for midi_function in midi_functions.midi_functions.into_iter() {
+    let controller_profiles: Vec<ControllerProfile> = args
+        .profiles
+        .iter()
+        .filter_map(|filepath| {
+            parse_file_to_controller_profile(filepath)
+                .map_err(|err| println!("{err}"))
+                .ok()
+        })
+        .collect();
+    let midi_mapping = args
+        .mapping
+        .as_ref()
+        .map_or_else(MidiMappingFile::default, |filepath| {
+            parse_file_to_midi_mapping(filepath).unwrap_or_else(|err| {
+                println!("{err}");
+                std::process::exit(1);
+            })
+        });
+    let mut midi_functions_with_elements_ids: HashMap<String, Vec<MidiBinding>> = HashMap::new();
+    for midi_function in midi_functions.midi_functions.iter() {
         midi_functions_with_elements_ids.insert(midi_function.get_name(), Vec::new());
-        midi_functions_set.insert(midi_function);
+    }
+    for (name, bindings) in midi_mapping.midi_functions {
+        if !midi_functions_with_elements_ids.contains_key(&name) {
+            midi_functions
+                .midi_functions
+                .push(MidiFunction::new(name.clone()));
+        }
+        midi_functions_with_elements_ids.insert(name, bindings);
     }
     let midi_elements_gui = MidiElementsGui {
         midi_receiver: Some(midi_receiver),
         midi_thread: Some(jack_midi_thread),
         tx_close: Some(tx_close),
         n_items: 0,
-        midi_functions: midi_functions_set,
+        midi_functions: midi_functions.midi_functions,
+        functions_path: args.filepath.unwrap_or_default(),
+        new_function_name: String::new(),
+        renamed_function: None,
         midi_functions_with_elements_ids,
+        mapping_path: args.mapping.unwrap_or_default(),
         midi_elements_map: HashMap::new(),
         selected_midi_function: None,
         last_midi_msg: None,
         num_detected_midi_ids: 0,
+        connection_manager,
+        show_connections: false,
+        control_classifiers: HashMap::new(),
+        midi_learn: None,
+        dragged: None,
+        controller_profiles,
+        show_profile: false,
+        show_layout: false,
+        show_pads: false,
+        profile_path: args.profiles.first().cloned().unwrap_or_default(),
+        status_text: String::new(),
+        edit_history: Default::default(),
+        show_history: false,
+        moved_control: None,
+        kept_conflicts: HashSet::new(),
     };
 
     let options = eframe::NativeOptions {
@@ -0,0 +1,558 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::midi_elements_gui::MidiElementsGui;
+use midi_mapper::{
+    control_address::ControlAddress,
+    controller_profile::{ControllerProfile, ProfileControl},
+    midi_function::MidiFunction,
+    midi_mapping::{BindingMode, MidiBinding},
+};
+use std::fmt;
+
+/// A change of the functions, their bindings or the layout which can be undone
+#[derive(Clone, Debug)]
+pub enum MappingEdit {
+    Bind {
+        midi_function: String,
+        idx: usize,
+        binding: MidiBinding,
+    },
+    Unbind {
+        midi_function: String,
+        idx: usize,
+        binding: MidiBinding,
+    },
+    SetMode {
+        midi_function: String,
+        idx: usize,
+        old_mode: Option<BindingMode>,
+        new_mode: Option<BindingMode>,
+    },
+    AddFunction {
+        idx: usize,
+        midi_function: MidiFunction,
+        bindings: Vec<MidiBinding>,
+    },
+    DeleteFunction {
+        idx: usize,
+        midi_function: MidiFunction,
+        bindings: Vec<MidiBinding>,
+    },
+    RenameFunction {
+        idx: usize,
+        old_name: String,
+        new_name: String,
+    },
+    SwapFunctions(usize, usize),
+    /// the control of the edited profile before and after the move,
+    /// `None` if it is not part of the profile
+    MoveControl {
+        address: ControlAddress,
+        old_control: Option<ProfileControl>,
+        new_control: Option<ProfileControl>,
+    },
+    /// edits undone at once, e.g. moving a binding to another function
+    Group(String, Vec<MappingEdit>),
+}
+
+impl MappingEdit {
+    pub fn inverse(&self) -> MappingEdit {
+        match self.clone() {
+            MappingEdit::Bind {
+                midi_function,
+                idx,
+                binding,
+            } => MappingEdit::Unbind {
+                midi_function,
+                idx,
+                binding,
+            },
+            MappingEdit::Unbind {
+                midi_function,
+                idx,
+                binding,
+            } => MappingEdit::Bind {
+                midi_function,
+                idx,
+                binding,
+            },
+            MappingEdit::SetMode {
+                midi_function,
+                idx,
+                old_mode,
+                new_mode,
+            } => MappingEdit::SetMode {
+                midi_function,
+                idx,
+                old_mode: new_mode,
+                new_mode: old_mode,
+            },
+            MappingEdit::AddFunction {
+                idx,
+                midi_function,
+                bindings,
+            } => MappingEdit::DeleteFunction {
+                idx,
+                midi_function,
+                bindings,
+            },
+            MappingEdit::DeleteFunction {
+                idx,
+                midi_function,
+                bindings,
+            } => MappingEdit::AddFunction {
+                idx,
+                midi_function,
+                bindings,
+            },
+            MappingEdit::RenameFunction {
+                idx,
+                old_name,
+                new_name,
+            } => MappingEdit::RenameFunction {
+                idx,
+                old_name: new_name,
+                new_name: old_name,
+            },
+            MappingEdit::SwapFunctions(idx0, idx1) => MappingEdit::SwapFunctions(idx0, idx1),
+            MappingEdit::MoveControl {
+                address,
+                old_control,
+                new_control,
+            } => MappingEdit::MoveControl {
+                address,
+                old_control: new_control,
+                new_control: old_control,
+            },
+            MappingEdit::Group(description, edits) => MappingEdit::Group(
+                description,
+                edits.iter().rev().map(MappingEdit::inverse).collect(),
+            ),
+        }
+    }
+
+    pub fn apply(&self, gui: &mut MidiElementsGui) {
+        match self {
+            MappingEdit::Bind {
+                midi_function,
+                idx,
+                binding,
+            } => {
+                if let Some(midi_bindings) =
+                    gui.midi_functions_with_elements_ids.get_mut(midi_function)
+                {
+                    midi_bindings.insert((*idx).min(midi_bindings.len()), binding.clone());
+                }
+            }
+            MappingEdit::Unbind {
+                midi_function, idx, ..
+            } => {
+                if let Some(midi_bindings) =
+                    gui.midi_functions_with_elements_ids.get_mut(midi_function)
+                {
+                    if *idx < midi_bindings.len() {
+                        midi_bindings.remove(*idx);
+                    }
+                }
+            }
+            MappingEdit::SetMode {
+                midi_function,
+                idx,
+                new_mode,
+                ..
+            } => {
+                if let Some(binding) = gui
+                    .midi_functions_with_elements_ids
+                    .get_mut(midi_function)
+                    .and_then(|midi_bindings| midi_bindings.get_mut(*idx))
+                {
+                    binding.mode = *new_mode;
+                }
+            }
+            MappingEdit::AddFunction {
+                idx,
+                midi_function,
+                bindings,
+            } => {
+                let idx = (*idx).min(gui.midi_functions.len());
+                gui.midi_functions.insert(idx, midi_function.clone());
+                gui.midi_functions_with_elements_ids
+                    .insert(midi_function.get_name(), bindings.clone());
+            }
+            MappingEdit::DeleteFunction {
+                idx, midi_function, ..
+            } => {
+                let name = midi_function.get_name();
+                if *idx < gui.midi_functions.len() {
+                    gui.midi_functions.remove(*idx);
+                }
+                gui.midi_functions_with_elements_ids.remove(&name);
+                if gui.selected_midi_function.as_ref() == Some(&name) {
+                    gui.selected_midi_function = None;
+                }
+                // the armed functions are looked up by name
+                gui.midi_learn = None;
+            }
+            MappingEdit::RenameFunction {
+                idx,
+                old_name,
+                new_name,
+            } => {
+                let midi_bindings = gui
+                    .midi_functions_with_elements_ids
+                    .remove(old_name)
+                    .unwrap_or_default();
+                gui.midi_functions_with_elements_ids
+                    .insert(new_name.clone(), midi_bindings);
+                if gui.selected_midi_function.as_ref() == Some(old_name) {
+                    gui.selected_midi_function = Some(new_name.clone());
+                }
+                if let Some(midi_function) = gui.midi_functions.get_mut(*idx) {
+                    midi_function.set_name(new_name.clone());
+                }
+                gui.midi_learn = None;
+            }
+            MappingEdit::SwapFunctions(idx0, idx1) => {
+                if *idx0 < gui.midi_functions.len() && *idx1 < gui.midi_functions.len() {
+                    gui.midi_functions.swap(*idx0, *idx1);
+                }
+            }
+            MappingEdit::MoveControl {
+                address,
+                new_control: Some(new_control),
+                ..
+            } => {
+                if gui.controller_profiles.is_empty() {
+                    gui.controller_profiles.push(ControllerProfile::default());
+                }
+                *gui.controller_profiles[0].add_control(address) = new_control.clone();
+            }
+            MappingEdit::MoveControl {
+                address,
+                new_control: None,
+                ..
+            } => {
+                if let Some(controller_profile) = gui.controller_profiles.first_mut() {
+                    controller_profile.remove_control(address);
+                    // the profile was created by the move
+                    if *controller_profile == ControllerProfile::default() {
+                        gui.controller_profiles.remove(0);
+                    }
+                }
+            }
+            MappingEdit::Group(_, edits) => {
+                for edit in edits {
+                    edit.apply(gui);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for MappingEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingEdit::Bind {
+                midi_function,
+                binding,
+                ..
+            } => write!(f, "bind {} to {}", binding, midi_function),
+            MappingEdit::Unbind {
+                midi_function,
+                binding,
+                ..
+            } => write!(f, "unbind {} from {}", binding, midi_function),
+            MappingEdit::SetMode {
+                midi_function,
+                idx,
+                new_mode,
+                ..
+            } => write!(
+                f,
+                "set mode of binding {} of {} to {}",
+                idx + 1,
+                midi_function,
+                new_mode.map_or("-".to_string(), |mode| mode.to_string())
+            ),
+            MappingEdit::AddFunction { midi_function, .. } => {
+                write!(f, "add function {}", midi_function.get_name())
+            }
+            MappingEdit::DeleteFunction { midi_function, .. } => {
+                write!(f, "delete function {}", midi_function.get_name())
+            }
+            MappingEdit::RenameFunction {
+                old_name, new_name, ..
+            } => write!(f, "rename {} to {}", old_name, new_name),
+            MappingEdit::SwapFunctions(idx0, idx1) => {
+                write!(f, "swap functions {} and {}", idx0 + 1, idx1 + 1)
+            }
+            MappingEdit::MoveControl { address, .. } => {
+                write!(f, "move {} on the layout", address)
+            }
+            MappingEdit::Group(description, _) => write!(f, "{}", description),
+        }
+    }
+}
+
+/// Done and undone edits, a new edit drops the undone ones
+#[derive(Default)]
+pub struct EditHistory {
+    done: Vec<MappingEdit>,
+    undone: Vec<MappingEdit>,
+}
+
+impl EditHistory {
+    /// Adds an edit which was already applied
+    pub fn record(&mut self, edit: MappingEdit) {
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    pub fn done(&self) -> &[MappingEdit] {
+        &self.done
+    }
+
+    /// The undone edits, the next one to redo is the last
+    pub fn undone(&self) -> &[MappingEdit] {
+        &self.undone
+    }
+
+    /// The edit to revert, it is kept for redo
+    pub fn undo(&mut self) -> Option<MappingEdit> {
+        let edit = self.done.pop()?;
+        self.undone.push(edit.clone());
+        Some(edit)
+    }
+
+    /// The edit to apply again
+    pub fn redo(&mut self) -> Option<MappingEdit> {
+        let edit = self.undone.pop()?;
+        self.done.push(edit.clone());
+        Some(edit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_mapper::{controller_profile::ControlPosition, midi_mapping::BindingControl};
+
+    fn cc(controller: u8) -> MidiBinding {
+        MidiBinding {
+            control: BindingControl::Cc {
+                channel: 1,
+                controller,
+            },
+            port: None,
+            device: None,
+            mode: None,
+        }
+    }
+
+    fn test_gui() -> MidiElementsGui {
+        let mut gui = MidiElementsGui::default();
+        for (name, bindings) in [("Volume", vec![cc(7), cc(8), cc(9)]), ("Pan", vec![cc(10)])] {
+            gui.midi_functions.push(MidiFunction::new(name.to_string()));
+            gui.midi_functions_with_elements_ids
+                .insert(name.to_string(), bindings);
+        }
+        gui
+    }
+
+    type GuiState = (
+        Vec<MidiFunction>,
+        Vec<(String, Vec<MidiBinding>)>,
+        Vec<ControllerProfile>,
+    );
+
+    fn state(gui: &MidiElementsGui) -> GuiState {
+        let mut midi_bindings: Vec<(String, Vec<MidiBinding>)> = gui
+            .midi_functions_with_elements_ids
+            .iter()
+            .map(|(name, bindings)| (name.clone(), bindings.clone()))
+            .collect();
+        midi_bindings.sort();
+        (
+            gui.midi_functions.clone(),
+            midi_bindings,
+            gui.controller_profiles.clone(),
+        )
+    }
+
+    fn bindings<'a>(gui: &'a MidiElementsGui, midi_function: &str) -> &'a [MidiBinding] {
+        &gui.midi_functions_with_elements_ids[midi_function]
+    }
+
+    /// applies the edit and its inverse and returns the state in between
+    fn apply_and_revert(gui: &mut MidiElementsGui, edit: &MappingEdit) -> GuiState {
+        let state_before = state(gui);
+        edit.apply(gui);
+        let state_edited = state(gui);
+        edit.inverse().apply(gui);
+        assert_eq!(state(gui), state_before);
+        state_edited
+    }
+
+    #[test]
+    fn unbind_restores_the_binding_at_its_idx() {
+        let mut gui = test_gui();
+        let unbind = MappingEdit::Unbind {
+            midi_function: "Volume".to_string(),
+            idx: 1,
+            binding: cc(8),
+        };
+        let (_, midi_bindings, _) = apply_and_revert(&mut gui, &unbind);
+        assert!(midi_bindings.contains(&("Volume".to_string(), vec![cc(7), cc(9)])));
+        assert_eq!(bindings(&gui, "Volume"), [cc(7), cc(8), cc(9)]);
+    }
+
+    #[test]
+    fn group_is_reverted_in_reverse_order() {
+        let mut gui = test_gui();
+        // binding and setting its mode only reverts when the mode is reset first
+        let bind_with_mode = MappingEdit::Group(
+            "bind with mode".to_string(),
+            vec![
+                MappingEdit::Bind {
+                    midi_function: "Volume".to_string(),
+                    idx: 0,
+                    binding: cc(11),
+                },
+                MappingEdit::SetMode {
+                    midi_function: "Volume".to_string(),
+                    idx: 0,
+                    old_mode: None,
+                    new_mode: Some(BindingMode::Absolute),
+                },
+            ],
+        );
+        let MappingEdit::Group(_, inverse_edits) = bind_with_mode.inverse() else {
+            panic!("the inverse of a group is a group");
+        };
+        assert!(matches!(
+            inverse_edits.as_slice(),
+            [MappingEdit::SetMode { .. }, MappingEdit::Unbind { .. }]
+        ));
+        apply_and_revert(&mut gui, &bind_with_mode);
+
+        let move_binding = MappingEdit::Group(
+            "move binding".to_string(),
+            vec![
+                MappingEdit::Unbind {
+                    midi_function: "Volume".to_string(),
+                    idx: 2,
+                    binding: cc(9),
+                },
+                MappingEdit::Bind {
+                    midi_function: "Pan".to_string(),
+                    idx: 1,
+                    binding: cc(9),
+                },
+            ],
+        );
+        let (_, midi_bindings, _) = apply_and_revert(&mut gui, &move_binding);
+        assert_eq!(
+            midi_bindings,
+            vec![
+                ("Pan".to_string(), vec![cc(10), cc(9)]),
+                ("Volume".to_string(), vec![cc(7), cc(8)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn functions_are_restored() {
+        let mut gui = test_gui();
+        let delete = MappingEdit::DeleteFunction {
+            idx: 0,
+            midi_function: MidiFunction::new("Volume".to_string()),
+            bindings: vec![cc(7), cc(8), cc(9)],
+        };
+        let (midi_functions, _, _) = apply_and_revert(&mut gui, &delete);
+        assert_eq!(midi_functions, vec![MidiFunction::new("Pan".to_string())]);
+        let rename = MappingEdit::RenameFunction {
+            idx: 1,
+            old_name: "Pan".to_string(),
+            new_name: "Balance".to_string(),
+        };
+        let (_, midi_bindings, _) = apply_and_revert(&mut gui, &rename);
+        assert_eq!(midi_bindings[0], ("Balance".to_string(), vec![cc(10)]));
+        apply_and_revert(&mut gui, &MappingEdit::SwapFunctions(0, 1));
+    }
+
+    #[test]
+    fn move_control_removes_the_profile_it_created() {
+        let mut gui = test_gui();
+        let address = ControlAddress::control_change(0, 7);
+        let moved_control = ProfileControl {
+            name: "Volume".to_string(),
+            address: address.clone(),
+            kind: None,
+            position: Some(ControlPosition { x: 1.0, y: 2.0 }),
+        };
+        let move_control = MappingEdit::MoveControl {
+            address: address.clone(),
+            old_control: None,
+            new_control: Some(moved_control.clone()),
+        };
+        let (_, _, controller_profiles) = apply_and_revert(&mut gui, &move_control);
+        assert_eq!(controller_profiles.len(), 1);
+        assert_eq!(controller_profiles[0].controls, vec![moved_control]);
+        assert!(gui.controller_profiles.is_empty());
+
+        // an existing profile is kept
+        gui.controller_profiles.push(ControllerProfile {
+            device: "mixer".to_string(),
+            ..Default::default()
+        });
+        apply_and_revert(&mut gui, &move_control);
+        assert_eq!(gui.controller_profiles.len(), 1);
+    }
+
+    #[test]
+    fn new_edit_after_undo_clears_redo() {
+        let mut gui = test_gui();
+        let state_before = state(&gui);
+        let unbind = MappingEdit::Unbind {
+            midi_function: "Volume".to_string(),
+            idx: 0,
+            binding: cc(7),
+        };
+        unbind.apply(&mut gui);
+        gui.edit_history.record(unbind);
+
+        let edit = gui.edit_history.undo().unwrap();
+        edit.inverse().apply(&mut gui);
+        assert_eq!(state(&gui), state_before);
+        assert!(gui.edit_history.done().is_empty());
+        assert_eq!(gui.edit_history.undone().len(), 1);
+
+        let edit = gui.edit_history.redo().unwrap();
+        edit.apply(&mut gui);
+        assert_eq!(bindings(&gui, "Volume"), [cc(8), cc(9)]);
+        assert!(gui.edit_history.undone().is_empty());
+
+        gui.edit_history.undo().unwrap().inverse().apply(&mut gui);
+        let swap = MappingEdit::SwapFunctions(0, 1);
+        swap.apply(&mut gui);
+        gui.edit_history.record(swap);
+        assert!(gui.edit_history.undone().is_empty());
+        assert!(gui.edit_history.redo().is_none());
+        assert_eq!(gui.edit_history.done().len(), 1);
+    }
+}
@@ -15,52 +15,999 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use eframe::egui::{self, ScrollArea, ViewportCommand};
+use crate::mapping_edit::{EditHistory, MappingEdit};
+use eframe::egui::{
+    self, Id, Key, KeyboardShortcut, Modifiers, Rect, RichText, ScrollArea, Sense, ViewportCommand,
+};
 use midi_mapper::{
+    binding_conflicts::{find_binding_conflicts, BindingConflict, BindingRef},
+    control_address::AddressKind,
+    control_address::ControlAddress,
+    control_classifier::{ControlClassifier, ControlGuess},
+    controller_profile::{
+        control_label, find_profile_control, write_controller_profile_to_file, ControlKind,
+        ControlPosition, ControllerProfile, ProfileControl,
+    },
+    drag_and_drop::{drag_source, drop_target},
+    jack_connection_panel::jack_connection_panel,
     jackmidi::{MidiMsgAdvanced, MidiMsgBase},
+    jackprocess::JackConnectionManager,
     midi_egui_elements::midi_id_value_indicator,
     midi_egui_elements::{
         midi_id_double_precision_value_indicator, midi_note_status_intensity_indicator,
+        midi_pad_grid, MidiPad, PadEvent,
     },
-    midi_function::MidiFunction,
-};
-use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    midi_function::{write_midi_functions_to_file, MidiFunction, MidiFunctionFile},
+    midi_learn::MidiLearn,
+    midi_mapping::{write_midi_mapping_to_file, BindingMode, MidiBinding, MidiMappingFile},
 };
+use std::collections::{HashMap, HashSet};
+
+/// Adds the control to the profile, a new control gets the guessed kind
+fn add_profile_control(
+    controller_profile: &mut ControllerProfile,
+    control_classifiers: &HashMap<ControlAddress, ControlClassifier>,
+    address: &ControlAddress,
+) {
+    let control = controller_profile.add_control(address);
+    if control.kind.is_none() {
+        control.kind = control_classifiers
+            .get(address)
+            .and_then(|control_classifier| control_classifier.guess())
+            .map(|control_guess| control_guess.control_kind());
+    }
+}
+
+/// Indicator of the current value of a detected element
+fn element_indicator(ui: &mut egui::Ui, midi_advanced_msg: &MidiMsgAdvanced, label: &str) {
+    match midi_advanced_msg {
+        MidiMsgAdvanced::MidiNoteOnOff(_, value, intensity) => ui.add(
+            midi_note_status_intensity_indicator(label, value, *intensity as u32),
+        ),
+        MidiMsgAdvanced::MidiControlIdValue(_, value) => {
+            ui.add(midi_id_value_indicator(label, *value as u32))
+        }
+        MidiMsgAdvanced::MidiControl2IdsValue(_, _, value) => ui.add(
+            midi_id_double_precision_value_indicator(label, *value as u32),
+        ),
+        MidiMsgAdvanced::MidiEmpty => ui.label(label),
+    };
+}
+
+enum FunctionEdit {
+    Swap(usize, usize),
+    Rename(usize, String),
+    Delete(usize),
+}
+
+enum ConflictResolution {
+    KeepBoth,
+    /// the binding of the selected function stays, the other one is unbound
+    Replace,
+    /// the other binding is moved to the selected function and replaces its binding
+    Move,
+}
+
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
 
+/// What is dragged between the detected elements, the functions and their bindings
+pub enum DragPayload {
+    /// binding of a detected element
+    Element(MidiBinding),
+    Function(String),
+    Binding {
+        midi_function: String,
+        idx: usize,
+    },
+}
+
+#[derive(Default)]
 pub struct MidiElementsGui {
     pub midi_receiver: Option<std::sync::mpsc::Receiver<Box<dyn MidiMsgBase>>>,
     pub midi_thread: Option<std::thread::JoinHandle<()>>,
     pub tx_close: Option<crossbeam_channel::Sender<bool>>,
     pub n_items: usize,
-    pub midi_functions: HashSet<MidiFunction>,
-    pub midi_functions_with_elements_ids: HashMap<String, Vec<MidiMsgAdvanced>>,
-    pub midi_elements_map: HashMap<u16, (usize, MidiMsgAdvanced)>,
+    pub midi_functions: Vec<MidiFunction>,
+    /// the functions are saved to this file
+    pub functions_path: String,
+    pub new_function_name: String,
+    /// index and edited name of the function being renamed
+    pub renamed_function: Option<(usize, String)>,
+    pub midi_functions_with_elements_ids: HashMap<String, Vec<MidiBinding>>,
+    /// the bindings are saved to this file
+    pub mapping_path: String,
+    pub midi_elements_map: HashMap<ControlAddress, (usize, MidiMsgAdvanced)>,
     pub selected_midi_function: Option<String>,
     pub last_midi_msg: Option<Box<dyn MidiMsgBase>>,
     pub num_detected_midi_ids: usize,
+    pub control_classifiers: HashMap<ControlAddress, ControlClassifier>,
+    pub midi_learn: Option<MidiLearn>,
+    pub dragged: Option<DragPayload>,
+    pub connection_manager: Option<JackConnectionManager>,
+    pub show_connections: bool,
+    /// the first profile is the one edited in the profile window
+    pub controller_profiles: Vec<ControllerProfile>,
+    pub show_profile: bool,
+    pub show_layout: bool,
+    pub show_pads: bool,
+    pub profile_path: String,
+    pub status_text: String,
+    pub edit_history: EditHistory,
+    pub show_history: bool,
+    /// control moved on the layout and its profile control before the move
+    pub moved_control: Option<(ControlAddress, Option<ProfileControl>)>,
+    /// conflicts the user decided to keep
+    pub kept_conflicts: HashSet<BindingConflict>,
 }
 
-impl Default for MidiElementsGui {
-    fn default() -> Self {
-        Self {
-            midi_receiver: None,
-            midi_thread: None,
-            tx_close: None,
-            n_items: 0,
-            midi_functions: HashSet::new(),
-            midi_functions_with_elements_ids: HashMap::new(),
-            midi_elements_map: HashMap::new(),
-            selected_midi_function: None,
-            last_midi_msg: None,
-            num_detected_midi_ids: 0,
+impl MidiElementsGui {
+    fn edit(&mut self, edit: MappingEdit) {
+        edit.apply(self);
+        self.edit_history.record(edit);
+    }
+
+    fn undo(&mut self) {
+        if let Some(edit) = self.edit_history.undo() {
+            edit.inverse().apply(self);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(edit) = self.edit_history.redo() {
+            edit.apply(self);
+        }
+    }
+
+    fn is_bound(&self, midi_function: &str, binding: &MidiBinding) -> bool {
+        self.midi_functions_with_elements_ids
+            .get(midi_function)
+            .is_some_and(|midi_bindings| {
+                midi_bindings
+                    .iter()
+                    .any(|midi_binding| midi_binding.without_mode() == binding.without_mode())
+            })
+    }
+
+    /// Adds the binding to the function unless the control is already bound to it
+    fn bind(&mut self, midi_function: &str, binding: MidiBinding) {
+        if self.is_bound(midi_function, &binding) {
+            return;
+        }
+        let Some(midi_bindings) = self.midi_functions_with_elements_ids.get(midi_function) else {
+            return;
+        };
+        self.edit(MappingEdit::Bind {
+            midi_function: midi_function.to_string(),
+            idx: midi_bindings.len(),
+            binding,
+        });
+    }
+
+    fn unbind(&mut self, midi_function: &str, idx: usize) -> Option<MappingEdit> {
+        let binding = self
+            .midi_functions_with_elements_ids
+            .get(midi_function)?
+            .get(idx)?
+            .clone();
+        Some(MappingEdit::Unbind {
+            midi_function: midi_function.to_string(),
+            idx,
+            binding,
+        })
+    }
+
+    fn conflicts(&self) -> Vec<BindingConflict> {
+        find_binding_conflicts(&self.midi_functions_with_elements_ids)
+            .into_iter()
+            .filter(|conflict| !self.kept_conflicts.contains(conflict))
+            .collect()
+    }
+
+    /// Applies the unbind of the binding and adds it to `edits`, returns its former index
+    fn apply_unbind(
+        &mut self,
+        binding_ref: &BindingRef,
+        edits: &mut Vec<MappingEdit>,
+    ) -> Option<usize> {
+        let idx = self
+            .midi_functions_with_elements_ids
+            .get(&binding_ref.midi_function)?
+            .iter()
+            .position(|binding| *binding == binding_ref.binding)?;
+        let unbind = self.unbind(&binding_ref.midi_function, idx)?;
+        unbind.apply(self);
+        edits.push(unbind);
+        Some(idx)
+    }
+
+    /// `own` is the binding of the selected function, `other` the one it conflicts with
+    fn resolve_conflict(
+        &mut self,
+        conflict: BindingConflict,
+        own: &BindingRef,
+        other: &BindingRef,
+        resolution: ConflictResolution,
+    ) {
+        let mut edits = Vec::new();
+        let description = match resolution {
+            ConflictResolution::KeepBoth => {
+                self.kept_conflicts.insert(conflict);
+                return;
+            }
+            ConflictResolution::Replace => {
+                self.apply_unbind(other, &mut edits);
+                format!(
+                    "replace {} of {} by {}",
+                    other.binding, other.midi_function, own.binding
+                )
+            }
+            ConflictResolution::Move => {
+                let Some(own_idx) = self.apply_unbind(own, &mut edits) else {
+                    return;
+                };
+                self.apply_unbind(other, &mut edits);
+                if let Some(midi_bindings) = self
+                    .midi_functions_with_elements_ids
+                    .get(&own.midi_function)
+                {
+                    let bind = MappingEdit::Bind {
+                        midi_function: own.midi_function.clone(),
+                        idx: own_idx.min(midi_bindings.len()),
+                        binding: other.binding.clone(),
+                    };
+                    bind.apply(self);
+                    edits.push(bind);
+                }
+                format!(
+                    "move {} from {} to {}",
+                    other.binding, other.midi_function, own.midi_function
+                )
+            }
+        };
+        if !edits.is_empty() {
+            // the edits are already applied
+            self.edit_history
+                .record(MappingEdit::Group(description, edits));
+        }
+    }
+
+    /// Conflicts of the selected function with the actions to resolve them
+    fn conflicts_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(midi_function) = self.selected_midi_function.clone() else {
+            return;
+        };
+        let conflicts: Vec<BindingConflict> = self
+            .conflicts()
+            .into_iter()
+            .filter(|conflict| conflict.involves(&midi_function))
+            .collect();
+        if conflicts.is_empty() {
+            return;
+        }
+        ui.separator();
+        let mut resolved = None;
+        for conflict in conflicts {
+            let (own, other) = if conflict.first.midi_function == midi_function {
+                (conflict.first.clone(), conflict.second.clone())
+            } else {
+                (conflict.second.clone(), conflict.first.clone())
+            };
+            ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", conflict));
+            ui.horizontal(|ui| {
+                if ui
+                    .button("keep both")
+                    .on_hover_text("don't warn about this conflict again")
+                    .clicked()
+                {
+                    resolved = Some((conflict.clone(), ConflictResolution::KeepBoth));
+                }
+                if ui
+                    .button("replace")
+                    .on_hover_text(format!(
+                        "unbind {} from {}",
+                        other.binding, other.midi_function
+                    ))
+                    .clicked()
+                {
+                    resolved = Some((conflict.clone(), ConflictResolution::Replace));
+                }
+                if ui
+                    .button("move")
+                    .on_hover_text(format!(
+                        "bind {} to {} instead of {}",
+                        other.binding, own.midi_function, own.binding
+                    ))
+                    .clicked()
+                {
+                    resolved = Some((conflict.clone(), ConflictResolution::Move));
+                }
+            });
+            if let Some((conflict, resolution)) = resolved.take() {
+                self.resolve_conflict(conflict, &own, &other, resolution);
+                break;
+            }
+        }
+    }
+
+    /// List of the edits, a click undoes or redoes up to the clicked edit
+    fn history_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !self.edit_history.done().is_empty(),
+                    egui::Button::new("undo"),
+                )
+                .on_hover_text(ui.ctx().format_shortcut(&UNDO_SHORTCUT))
+                .clicked()
+            {
+                self.undo();
+            }
+            if ui
+                .add_enabled(
+                    !self.edit_history.undone().is_empty(),
+                    egui::Button::new("redo"),
+                )
+                .on_hover_text(ui.ctx().format_shortcut(&REDO_SHORTCUT))
+                .clicked()
+            {
+                self.redo();
+            }
+        });
+        let mut num_undo = 0;
+        let mut num_redo = 0;
+        ScrollArea::vertical().show(ui, |ui| {
+            let num_done = self.edit_history.done().len();
+            for (idx, edit) in self.edit_history.done().iter().enumerate() {
+                if ui
+                    .selectable_label(idx + 1 == num_done, edit.to_string())
+                    .clicked()
+                {
+                    num_undo = num_done - idx - 1;
+                }
+            }
+            let num_undone = self.edit_history.undone().len();
+            for (idx, edit) in self.edit_history.undone().iter().enumerate().rev() {
+                let label = RichText::new(edit.to_string()).weak().strikethrough();
+                if ui.selectable_label(false, label).clicked() {
+                    num_redo = num_undone - idx;
+                }
+            }
+        });
+        for _ in 0..num_undo {
+            self.undo();
+        }
+        for _ in 0..num_redo {
+            self.redo();
+        }
+    }
+    /// detected addresses in the order of detection
+    fn detected_addresses(&self) -> Vec<ControlAddress> {
+        let mut detected_addresses: Vec<(usize, &ControlAddress)> = self
+            .midi_elements_map
+            .iter()
+            .map(|(address, (detection_number, _))| (*detection_number, address))
+            .collect();
+        detected_addresses.sort();
+        detected_addresses
+            .into_iter()
+            .map(|(_, address)| address.clone())
+            .collect()
+    }
+
+    /// Names of the functions with a binding to the control at `address`
+    fn bound_functions(&self, address: &ControlAddress) -> Vec<&str> {
+        let mut bound_functions: Vec<&str> = self
+            .midi_functions_with_elements_ids
+            .iter()
+            .filter(|(_, midi_bindings)| {
+                midi_bindings.iter().any(|midi_binding| {
+                    let binding_address = &midi_binding.addresses()[0];
+                    binding_address.kind == address.kind
+                        && binding_address.channel == address.channel
+                        && binding_address.number == address.number
+                        && (midi_binding.port.is_none() || midi_binding.port == address.port)
+                })
+            })
+            .map(|(midi_function, _)| midi_function.as_str())
+            .collect();
+        bound_functions.sort();
+        bound_functions
+    }
+
+    /// The profile edited in the profile and layout windows
+    fn edited_profile(&mut self) -> &mut ControllerProfile {
+        if self.controller_profiles.is_empty() {
+            self.controller_profiles.push(ControllerProfile::default());
+        }
+        &mut self.controller_profiles[0]
+    }
+
+    fn save_profile_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::TextEdit::singleline(&mut self.profile_path).hint_text("profile.json"));
+        if ui.button("save profile").clicked() {
+            let profile_path = self.profile_path.clone();
+            self.status_text =
+                match write_controller_profile_to_file(&profile_path, self.edited_profile()) {
+                    Ok(()) => format!("profile written to {}", profile_path),
+                    Err(err) => err,
+                };
+        }
+    }
+
+    /// Canvas with the detected elements at their profile positions, elements are moved by dragging.
+    /// Elements without position are placed in rows below the placed ones.
+    /// Detected notes and buttons as pads, pressing a pad selects its function
+    fn pads_ui(&mut self, ui: &mut egui::Ui) {
+        let addresses: Vec<ControlAddress> = self
+            .detected_addresses()
+            .into_iter()
+            .filter(|address| {
+                address.kind == AddressKind::Note
+                    || self
+                        .control_classifiers
+                        .get(address)
+                        .and_then(ControlClassifier::guess)
+                        == Some(ControlGuess::Button)
+            })
+            .collect();
+        if addresses.is_empty() {
+            ui.label("press some pads or buttons");
+            return;
+        }
+        let pads: Vec<MidiPad> = addresses
+            .iter()
+            .map(|address| {
+                let mut pad = MidiPad::new(
+                    control_label(&self.controller_profiles, address),
+                    Some(address.clone()),
+                );
+                if let Some((_, midi_advanced_msg)) = self.midi_elements_map.get(address) {
+                    pad.update_from_midi_msg(midi_advanced_msg);
+                }
+                // unbound pads are grey
+                if self.bound_functions(address).is_empty() {
+                    pad.color = Some(ui.visuals().widgets.inactive.bg_fill);
+                }
+                pad
+            })
+            .collect();
+        let pad_grid_response = midi_pad_grid(ui, "detected pads", &pads, 8);
+        for pad_event in pad_grid_response.events {
+            if let PadEvent::Pressed { idx, .. } = pad_event {
+                let bound_function = self
+                    .bound_functions(&addresses[idx])
+                    .first()
+                    .map(|midi_function| midi_function.to_string());
+                if bound_function.is_some() {
+                    self.selected_midi_function = bound_function;
+                }
+            }
+        }
+    }
+
+    fn layout_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| self.save_profile_ui(ui));
+        ui.label(&self.status_text);
+        let cell_size = ui.spacing().interact_size.y * egui::vec2(8.5, 2.5);
+        let detected_addresses = self.detected_addresses();
+        let positions: Vec<Option<ControlPosition>> = detected_addresses
+            .iter()
+            .map(|address| {
+                find_profile_control(&self.controller_profiles, address)
+                    .and_then(|control| control.position)
+            })
+            .collect();
+        let free_row = positions
+            .iter()
+            .flatten()
+            .map(|position| position.y.floor() + 1.0)
+            .fold(0.0, f32::max);
+        let mut num_unplaced = 0;
+        let positions: Vec<ControlPosition> = positions
+            .into_iter()
+            .map(|position| {
+                position.unwrap_or_else(|| {
+                    num_unplaced += 1;
+                    ControlPosition {
+                        x: ((num_unplaced - 1) % 8) as f32,
+                        y: free_row + ((num_unplaced - 1) / 8) as f32,
+                    }
+                })
+            })
+            .collect();
+        // at least the 8 columns of the unplaced elements
+        let canvas_size = positions
+            .iter()
+            .fold(cell_size * egui::vec2(8.0, 4.0), |size, position| {
+                size.max(egui::vec2(position.x + 1.0, position.y + 1.0) * cell_size)
+            });
+        ScrollArea::both().drag_to_scroll(false).show(ui, |ui| {
+            let (canvas_rect, _) = ui.allocate_exact_size(canvas_size, Sense::hover());
+            for (address, position) in detected_addresses.iter().zip(positions) {
+                let Some((_, midi_advanced_msg)) = self.midi_elements_map.get(address) else {
+                    continue;
+                };
+                let rect = Rect::from_min_size(
+                    canvas_rect.min + egui::vec2(position.x, position.y) * cell_size,
+                    cell_size,
+                );
+                let label = control_label(&self.controller_profiles, address);
+                let bound_functions = self.bound_functions(address).join(", ");
+                ui.allocate_ui_at_rect(rect, |ui| {
+                    element_indicator(ui, midi_advanced_msg, &label);
+                    ui.small(bound_functions);
+                });
+                let response =
+                    ui.interact(rect, Id::new(("layout element", address)), Sense::drag());
+                if response.drag_started() {
+                    let old_control = self
+                        .controller_profiles
+                        .first()
+                        .and_then(|controller_profile| controller_profile.find_control(address))
+                        .cloned();
+                    self.moved_control = Some((address.clone(), old_control));
+                }
+                if response.drag_released() {
+                    if let Some((address, old_control)) = self.moved_control.take() {
+                        let new_control = self
+                            .controller_profiles
+                            .first()
+                            .and_then(|controller_profile| {
+                                controller_profile.find_control(&address)
+                            })
+                            .cloned();
+                        // the control was moved while dragging
+                        self.edit_history.record(MappingEdit::MoveControl {
+                            address,
+                            old_control,
+                            new_control,
+                        });
+                    }
+                }
+                if response.dragged() {
+                    let delta = response.drag_delta() / cell_size;
+                    self.edited_profile();
+                    let control_classifiers = &self.control_classifiers;
+                    let controller_profile = &mut self.controller_profiles[0];
+                    add_profile_control(controller_profile, control_classifiers, address);
+                    if let Some(control) = controller_profile.find_control_mut(address) {
+                        control.position = Some(ControlPosition {
+                            x: (position.x + delta.x).max(0.0),
+                            y: (position.y + delta.y).max(0.0),
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    fn profile_ui(&mut self, ui: &mut egui::Ui) {
+        let detected_addresses = self.detected_addresses();
+        ui.horizontal(|ui| {
+            ui.label("device:");
+            ui.text_edit_singleline(&mut self.edited_profile().device);
+        });
+        ui.horizontal(|ui| {
+            self.save_profile_ui(ui);
+            if ui.button("add all detected").clicked() {
+                let control_classifiers = &self.control_classifiers;
+                let controller_profile = &mut self.controller_profiles[0];
+                for address in detected_addresses.iter() {
+                    add_profile_control(controller_profile, control_classifiers, address);
+                }
+            }
+        });
+        ui.label(&self.status_text);
+        let controller_profile = &mut self.controller_profiles[0];
+        let control_classifiers = &self.control_classifiers;
+        ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("profile controls")
+                .striped(true)
+                .show(ui, |ui| {
+                    for address in detected_addresses.iter() {
+                        ui.label(address.to_string());
+                        if let Some(control) = controller_profile.find_control_mut(address) {
+                            ui.text_edit_singleline(&mut control.name);
+                            egui::ComboBox::from_id_source(address)
+                                .selected_text(
+                                    control
+                                        .kind
+                                        .map_or("-".to_string(), |kind| kind.to_string()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut control.kind, None, "-");
+                                    for kind in ControlKind::ALL {
+                                        ui.selectable_value(
+                                            &mut control.kind,
+                                            Some(kind),
+                                            kind.to_string(),
+                                        );
+                                    }
+                                });
+                        } else if ui.button("add").clicked() {
+                            add_profile_control(controller_profile, control_classifiers, address);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn learn_ui(&mut self, ui: &mut egui::Ui) {
+        match self.midi_learn {
+            Some(ref mut midi_learn) => {
+                ui.label(format!(
+                    "move a control for {} ({} left)",
+                    midi_learn.armed_function().unwrap_or_default(),
+                    midi_learn.remaining()
+                ));
+                if ui.button("skip").clicked() {
+                    midi_learn.skip();
+                }
+                if ui.button("stop learning").clicked() || midi_learn.is_done() {
+                    self.midi_learn = None;
+                }
+            }
+            None => {
+                let learn_button = ui.add_enabled(
+                    self.selected_midi_function.is_some(),
+                    egui::Button::new("learn"),
+                );
+                if learn_button.clicked() {
+                    self.midi_learn = self
+                        .selected_midi_function
+                        .clone()
+                        .map(|midi_function| MidiLearn::new(vec![midi_function]));
+                }
+                if ui.button("learn all").clicked() {
+                    self.midi_learn = Some(MidiLearn::new(
+                        self.midi_functions
+                            .iter()
+                            .map(|midi_function| midi_function.get_name())
+                            .collect(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Binding of a detected element with the mode of the guessed kind
+    fn element_binding(&self, midi_advanced_msg: &MidiMsgAdvanced) -> Option<MidiBinding> {
+        let mut binding = MidiBinding::from_midi_msg_advanced(midi_advanced_msg)?;
+        binding.mode = midi_advanced_msg
+            .get_address()
+            .and_then(|address| self.control_classifiers.get(address))
+            .and_then(|control_classifier| control_classifier.guess())
+            .map(|control_guess| control_guess.binding_mode());
+        Some(binding)
+    }
+
+    /// Drops the dragged element or binding on `midi_function`, a binding is moved
+    fn drop_on_function(&mut self, midi_function: &str) {
+        match self.dragged.take() {
+            Some(DragPayload::Element(binding)) => self.bind(midi_function, binding),
+            Some(DragPayload::Binding {
+                midi_function: from_midi_function,
+                idx,
+            }) => {
+                let Some(unbind) = self.unbind(&from_midi_function, idx) else {
+                    return;
+                };
+                let MappingEdit::Unbind { ref binding, .. } = unbind else {
+                    return;
+                };
+                let Some(midi_bindings) = self.midi_functions_with_elements_ids.get(midi_function)
+                else {
+                    return;
+                };
+                // a binding already bound to the target function stays where it is
+                if self.is_bound(midi_function, binding) {
+                    return;
+                }
+                let bind = MappingEdit::Bind {
+                    midi_function: midi_function.to_string(),
+                    idx: midi_bindings.len(),
+                    binding: binding.clone(),
+                };
+                let description = format!(
+                    "move {} from {} to {}",
+                    binding, from_midi_function, midi_function
+                );
+                self.edit(MappingEdit::Group(description, vec![unbind, bind]));
+            }
+            _ => return,
+        }
+        self.selected_midi_function = Some(midi_function.to_string());
+    }
+
+    /// Checks a new name for a function, returns the reason if it can't be used
+    fn check_function_name(&self, name: &str) -> Result<(), String> {
+        if name.trim().is_empty() {
+            Err("the function name is empty".to_string())
+        } else if self
+            .midi_functions
+            .iter()
+            .any(|midi_function| midi_function.get_name() == name)
+        {
+            Err(format!("the function {} exists already", name))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_function(&mut self) {
+        let name = self.new_function_name.trim().to_string();
+        if let Err(err) = self.check_function_name(&name) {
+            self.status_text = err;
+            return;
+        }
+        self.edit(MappingEdit::AddFunction {
+            idx: self.midi_functions.len(),
+            midi_function: MidiFunction::new(name),
+            bindings: Vec::new(),
+        });
+        self.new_function_name.clear();
+    }
+
+    /// Renames the function, its bindings are kept
+    fn rename_function(&mut self, idx: usize, name: String) {
+        let name = name.trim().to_string();
+        let old_name = self.midi_functions[idx].get_name();
+        if name == old_name {
+            return;
+        }
+        if let Err(err) = self.check_function_name(&name) {
+            self.status_text = err;
+            return;
+        }
+        self.edit(MappingEdit::RenameFunction {
+            idx,
+            old_name,
+            new_name: name,
+        });
+    }
+
+    fn delete_function(&mut self, idx: usize) {
+        let midi_function = self.midi_functions[idx].clone();
+        let bindings = self
+            .midi_functions_with_elements_ids
+            .get(&midi_function.get_name())
+            .cloned()
+            .unwrap_or_default();
+        self.edit(MappingEdit::DeleteFunction {
+            idx,
+            midi_function,
+            bindings,
+        });
+    }
+
+    fn save_functions(&mut self) {
+        let midi_function_file = MidiFunctionFile {
+            midi_functions: self.midi_functions.clone(),
+        };
+        self.status_text =
+            match write_midi_functions_to_file(&self.functions_path, &midi_function_file) {
+                Ok(()) => format!("functions written to {}", self.functions_path),
+                Err(err) => err,
+            };
+    }
+
+    fn save_mapping(&mut self) {
+        let midi_mapping = MidiMappingFile::from_bindings(&self.midi_functions_with_elements_ids);
+        self.status_text = match write_midi_mapping_to_file(&self.mapping_path, &midi_mapping) {
+            Ok(()) => format!("mapping written to {}", self.mapping_path),
+            Err(err) => err,
+        };
+    }
+
+    fn functions_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.functions_path).hint_text("functions.json"),
+            );
+            if ui.button("save functions").clicked() {
+                self.save_functions();
+            }
+        });
+        ui.label(&self.status_text);
+        let mut function_edit = None;
+        let num_functions = self.midi_functions.len();
+        for idx in 0..num_functions {
+            let midi_function = self.midi_functions[idx].get_name();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(idx > 0, egui::Button::new("⏶")).clicked() {
+                    function_edit = Some(FunctionEdit::Swap(idx - 1, idx));
+                }
+                if ui
+                    .add_enabled(idx + 1 < num_functions, egui::Button::new("⏷"))
+                    .clicked()
+                {
+                    function_edit = Some(FunctionEdit::Swap(idx, idx + 1));
+                }
+                match self.renamed_function {
+                    Some((renamed_idx, ref mut name)) if renamed_idx == idx => {
+                        let response = ui.text_edit_singleline(name);
+                        if ui.button("ok").clicked()
+                            || (response.lost_focus()
+                                && ui.input(|input| input.key_pressed(egui::Key::Enter)))
+                        {
+                            function_edit = Some(FunctionEdit::Rename(idx, name.clone()));
+                        }
+                        if ui.button("cancel").clicked() {
+                            self.renamed_function = None;
+                        }
+                    }
+                    _ => {
+                        self.function_ui(ui, &midi_function);
+                        if ui.small_button("rename").clicked() {
+                            self.renamed_function = Some((idx, midi_function.clone()));
+                        }
+                        if ui.small_button("delete").clicked() {
+                            function_edit = Some(FunctionEdit::Delete(idx));
+                        }
+                    }
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.new_function_name).hint_text("new function"),
+            );
+            if ui.button("add").clicked()
+                || (response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)))
+            {
+                self.add_function();
+            }
+        });
+        match function_edit {
+            Some(FunctionEdit::Swap(idx0, idx1)) => {
+                self.edit(MappingEdit::SwapFunctions(idx0, idx1))
+            }
+            Some(FunctionEdit::Rename(idx, name)) => {
+                self.renamed_function = None;
+                self.rename_function(idx, name);
+            }
+            Some(FunctionEdit::Delete(idx)) => self.delete_function(idx),
+            None => {}
+        }
+    }
+
+    /// A function is dragged onto elements, elements and bindings are dropped on it
+    fn function_ui(&mut self, ui: &mut egui::Ui, midi_function: &str) {
+        let can_accept = match self.dragged {
+            Some(DragPayload::Element(_)) => true,
+            Some(DragPayload::Binding {
+                midi_function: ref from_midi_function,
+                ..
+            }) => from_midi_function != midi_function,
+            _ => false,
+        };
+        let dropped = drop_target(ui, can_accept, |ui| {
+            let drag_response = drag_source(ui, Id::new(("midi function", midi_function)), |ui| {
+                ui.selectable_label(
+                    self.selected_midi_function.as_deref() == Some(midi_function),
+                    midi_function,
+                )
+                .clicked()
+            });
+            if drag_response.inner {
+                self.selected_midi_function = Some(midi_function.to_string());
+            }
+            if drag_response.response.drag_started() {
+                self.dragged = Some(DragPayload::Function(midi_function.to_string()));
+            }
+        })
+        .inner;
+        if dropped {
+            self.drop_on_function(midi_function);
+        }
+    }
+
+    fn element_ui(&mut self, ui: &mut egui::Ui, midi_advanced_msg: &MidiMsgAdvanced) {
+        let Some(address) = midi_advanced_msg.get_address() else {
+            return;
+        };
+        let label = control_label(&self.controller_profiles, address);
+        let control_guess = self
+            .control_classifiers
+            .get(address)
+            .and_then(|control_classifier| control_classifier.guess());
+        let can_accept = matches!(self.dragged, Some(DragPayload::Function(_)));
+        let dropped = drop_target(ui, can_accept, |ui| {
+            let drag_response = drag_source(ui, Id::new(("midi element", address)), |ui| {
+                ui.horizontal(|ui| {
+                    element_indicator(ui, midi_advanced_msg, &label);
+                    ui.label(
+                        control_guess
+                            .map_or("?".to_string(), |control_guess| control_guess.to_string()),
+                    );
+                });
+            });
+            if drag_response.response.drag_started() {
+                self.dragged = self
+                    .element_binding(midi_advanced_msg)
+                    .map(DragPayload::Element);
+            }
+        })
+        .inner;
+        if dropped {
+            if let Some(DragPayload::Function(midi_function)) = self.dragged.take() {
+                if let Some(binding) = self.element_binding(midi_advanced_msg) {
+                    self.bind(&midi_function, binding);
+                }
+                self.selected_midi_function = Some(midi_function);
+            }
+        }
+    }
+
+    fn bindings_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(ref midi_function) = self.selected_midi_function else {
+            return;
+        };
+        let Some(selected_midi_events) = self.midi_functions_with_elements_ids.get(midi_function)
+        else {
+            return;
+        };
+        // dropping a binding back on the list keeps it
+        let can_accept = matches!(self.dragged, Some(DragPayload::Binding { .. }));
+        let mut dragged = None;
+        let mut mode_edit = None;
+        let dropped = drop_target(ui, can_accept, |ui| {
+            for (idx, binding) in selected_midi_events.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let drag_response =
+                        drag_source(ui, Id::new(("binding", midi_function, idx)), |ui| {
+                            ui.label(binding.without_mode().to_string())
+                        });
+                    if drag_response.response.drag_started() {
+                        dragged = Some(DragPayload::Binding {
+                            midi_function: midi_function.clone(),
+                            idx,
+                        });
+                    }
+                    let mut mode = binding.mode;
+                    egui::ComboBox::from_id_source((midi_function, idx))
+                        .selected_text(mode.map_or("-".to_string(), |mode| mode.to_string()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut mode, None, "-");
+                            for binding_mode in BindingMode::ALL {
+                                ui.selectable_value(
+                                    &mut mode,
+                                    Some(binding_mode),
+                                    binding_mode.to_string(),
+                                );
+                            }
+                        });
+                    if mode != binding.mode {
+                        mode_edit = Some(MappingEdit::SetMode {
+                            midi_function: midi_function.clone(),
+                            idx,
+                            old_mode: binding.mode,
+                            new_mode: mode,
+                        });
+                    }
+                });
+            }
+            ui.allocate_space(ui.available_size());
+        })
+        .inner;
+        if dropped {
+            self.dragged = None;
+        }
+        if dragged.is_some() {
+            self.dragged = dragged;
+        }
+        if let Some(mode_edit) = mode_edit {
+            self.edit(mode_edit);
         }
     }
 }
 
 impl eframe::App for MidiElementsGui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut learned_bindings = Vec::new();
         if let Some(ref midi_receiver) = self.midi_receiver {
             while let Ok(current_midi_msg) = midi_receiver.try_recv() {
                 let midi_advanced_msg = MidiMsgAdvanced::from_current_and_last_opt_midi_msgs((
@@ -68,18 +1015,44 @@ impl eframe::App for MidiElementsGui {
                     &mut self.last_midi_msg,
                 ));
                 if let Some(midi_advanced_msg) = midi_advanced_msg {
-                    let id = midi_advanced_msg.get_id();
-                    if let Some((detection_number, _)) = self.midi_elements_map.get(&id) {
+                    let Some(address) = midi_advanced_msg.get_address().cloned() else {
+                        continue;
+                    };
+                    self.control_classifiers
+                        .entry(address.clone())
+                        .or_default()
+                        .push(&midi_advanced_msg);
+                    let learned = self
+                        .midi_learn
+                        .as_mut()
+                        .and_then(|midi_learn| midi_learn.push(&midi_advanced_msg));
+                    if let Some(learned) = learned {
+                        learned_bindings.push(learned);
+                    }
+                    if let Some((detection_number, _)) = self.midi_elements_map.get(&address) {
                         self.midi_elements_map
-                            .insert(id, (*detection_number, midi_advanced_msg));
+                            .insert(address, (*detection_number, midi_advanced_msg));
                     } else {
                         self.midi_elements_map
-                            .insert(id, (self.num_detected_midi_ids, midi_advanced_msg));
+                            .insert(address, (self.num_detected_midi_ids, midi_advanced_msg));
                         self.num_detected_midi_ids += 1;
                     }
                 }
             }
         }
+        for (midi_function, binding) in learned_bindings {
+            self.bind(&midi_function, binding);
+            self.selected_midi_function = Some(midi_function);
+        }
+        // text fields keep their own undo
+        if !ctx.wants_keyboard_input() {
+            // the redo shortcut is checked first, it contains the undo shortcut
+            if ctx.input_mut(|input| input.consume_shortcut(&REDO_SHORTCUT)) {
+                self.redo();
+            } else if ctx.input_mut(|input| input.consume_shortcut(&UNDO_SHORTCUT)) {
+                self.undo();
+            }
+        }
         egui::TopBottomPanel::top("control").show(ctx, |ui| {
             ui.vertical(|ui| {
                 ui.heading("MidiElementsGui");
@@ -87,35 +1060,76 @@ impl eframe::App for MidiElementsGui {
                     if ui.button("close").clicked() {
                         if let Some(x) = &self.tx_close {
                             x.send(false).unwrap();
+                            if let Some(midi_thread) = self.midi_thread.take() {
+                                let _ = midi_thread.join();
+                            }
                             ctx.send_viewport_cmd(ViewportCommand::Close)
                         };
                     }
-                    let mut json_path = "".to_string();
-                    let _ = ui.add(egui::TextEdit::singleline(&mut json_path));
-                    if ui.button("export as json").clicked() {
-                        let _path = PathBuf::from(json_path);
-                        let json_output =
-                            serde_json::to_string(&self.midi_functions_with_elements_ids).unwrap();
-                        println!("{}", json_output);
+                    if self.connection_manager.is_some() {
+                        ui.toggle_value(&mut self.show_connections, "connections");
+                    }
+                    ui.toggle_value(&mut self.show_profile, "profile");
+                    ui.toggle_value(&mut self.show_layout, "layout");
+                    ui.toggle_value(&mut self.show_pads, "pads");
+                    ui.toggle_value(&mut self.show_history, "history");
+                    self.learn_ui(ui);
+                    let conflicts = self.conflicts();
+                    if !conflicts.is_empty() {
+                        let conflicts_text: Vec<String> =
+                            conflicts.iter().map(ToString::to_string).collect();
+                        let button = ui
+                            .button(format!("⚠ {} conflicts", conflicts.len()))
+                            .on_hover_text(conflicts_text.join("\n"));
+                        if button.clicked() {
+                            self.selected_midi_function =
+                                Some(conflicts[0].first.midi_function.clone());
+                        }
+                    }
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.mapping_path)
+                            .hint_text("mapping.json"),
+                    );
+                    if ui.button("save mapping").clicked() {
+                        self.save_mapping();
                     }
                 });
             });
         });
+        if let Some(ref connection_manager) = self.connection_manager {
+            egui::Window::new("jack connections")
+                .open(&mut self.show_connections)
+                .show(ctx, |ui| jack_connection_panel(ui, connection_manager));
+        }
+        let mut show_profile = self.show_profile;
+        egui::Window::new("controller profile")
+            .open(&mut show_profile)
+            .show(ctx, |ui| self.profile_ui(ui));
+        self.show_profile = show_profile;
+        let mut show_layout = self.show_layout;
+        egui::Window::new("controller layout")
+            .open(&mut show_layout)
+            .default_size([600.0, 400.0])
+            .show(ctx, |ui| self.layout_ui(ui));
+        self.show_layout = show_layout;
+        let mut show_pads = self.show_pads;
+        egui::Window::new("pads")
+            .open(&mut show_pads)
+            .show(ctx, |ui| self.pads_ui(ui));
+        self.show_pads = show_pads;
+        let mut show_history = self.show_history;
+        egui::Window::new("edit history")
+            .open(&mut show_history)
+            .show(ctx, |ui| self.history_ui(ui));
+        self.show_history = show_history;
         // show list of offered midi functions
-        egui::CentralPanel::default().show(ctx, |ui| {
-            for midi_function in self.midi_functions.iter() {
-                if ui.button(format!("{}", midi_function)).clicked() {
-                    let midi_function_name = midi_function.get_name();
-                    self.selected_midi_function = Some(midi_function_name);
-                }
-            }
-        });
+        egui::CentralPanel::default().show(ctx, |ui| self.functions_ui(ui));
         let len_hashmap = self.midi_elements_map.len();
         self.n_items = self.midi_elements_map.len();
-        let mut midi_elements_vec: Vec<(u16, MidiMsgAdvanced)> =
-            vec![(0, MidiMsgAdvanced::MidiEmpty); len_hashmap];
-        for (key, (detection_number, midi_advanced_messsage)) in self.midi_elements_map.iter() {
-            midi_elements_vec[*detection_number] = (*key, midi_advanced_messsage.clone());
+        let mut midi_elements_vec: Vec<MidiMsgAdvanced> =
+            vec![MidiMsgAdvanced::MidiEmpty; len_hashmap];
+        for (detection_number, midi_advanced_messsage) in self.midi_elements_map.values() {
+            midi_elements_vec[*detection_number] = midi_advanced_messsage.clone();
         }
 
         // show list of Midi events
@@ -125,7 +1139,6 @@ impl eframe::App for MidiElementsGui {
             let window_height = window_rect.height();
 
             ui.vertical(|ui| {
-                // self.n_items = self.midi_elements_map.len();
                 let text_style = egui::TextStyle::Body;
                 let row_height = ui.text_style_height(&text_style);
                 ScrollArea::vertical()
@@ -134,93 +1147,10 @@ impl eframe::App for MidiElementsGui {
                     .max_height(window_height - 60.0)
                     .min_scrolled_width(window_width - 40.0)
                     .max_width(window_width - 40.0)
+                    .drag_to_scroll(false)
                     .show_rows(ui, row_height, self.n_items, |ui, row_range| {
-                        for (row, (_key, midi_advanced_msg)) in midi_elements_vec.iter().enumerate()
-                        {
-                            if row_range.contains(&row) {
-                                if let MidiMsgAdvanced::MidiNoteOnOff(
-                                    id0,
-                                    id1,
-                                    value,
-                                    note,
-                                    intensity,
-                                ) = midi_advanced_msg
-                                {
-                                    if ui
-                                        .add(midi_note_status_intensity_indicator(
-                                            *id0 as u32,
-                                            &value,
-                                            *intensity as u32,
-                                        ))
-                                        .clicked()
-                                    {
-                                        if let Some(ref selected_midi_function) =
-                                            self.selected_midi_function
-                                        {
-                                            if let Some(ref mut midi_elements_id) = self
-                                                .midi_functions_with_elements_ids
-                                                .get_mut(selected_midi_function)
-                                            {
-                                                let midi_advancecd_msg =
-                                                    MidiMsgAdvanced::MidiNoteOnOff(
-                                                        *id0, *id1, false, *note, *intensity,
-                                                    );
-                                                midi_elements_id.push(midi_advancecd_msg);
-                                            }
-                                        }
-                                    }
-                                };
-
-                                if let MidiMsgAdvanced::MidiControlIdValue(id, value) =
-                                    midi_advanced_msg
-                                {
-                                    if ui
-                                        .add(midi_id_value_indicator(*id as u32, *value as u32))
-                                        .clicked()
-                                    {
-                                        if let Some(ref selected_midi_function) =
-                                            self.selected_midi_function
-                                        {
-                                            if let Some(ref mut midi_elements_id) = self
-                                                .midi_functions_with_elements_ids
-                                                .get_mut(selected_midi_function)
-                                            {
-                                                let midi_advancecd_msg =
-                                                    MidiMsgAdvanced::MidiControlIdValue(*id, 0);
-                                                midi_elements_id.push(midi_advancecd_msg);
-                                            }
-                                        }
-                                    }
-                                }
-                                if let MidiMsgAdvanced::MidiControl2IdsValue(id0, id1, value) =
-                                    midi_advanced_msg
-                                {
-                                    if ui
-                                        .add(midi_id_double_precision_value_indicator(
-                                            *id0 as u32,
-                                            *value as u32,
-                                        ))
-                                        .clicked()
-                                    {
-                                        if let Some(ref selected_midi_function) =
-                                            self.selected_midi_function
-                                        {
-                                            if let Some(ref mut midi_elements_id) = self
-                                                .midi_functions_with_elements_ids
-                                                .get_mut(selected_midi_function)
-                                            {
-                                                let midi_advancecd_msg =
-                                                    MidiMsgAdvanced::MidiControl2IdsValue(
-                                                        *id0, *id1, 0,
-                                                    );
-                                                midi_elements_id.push(midi_advancecd_msg);
-                                            }
-                                        }
-                                    }
-                                }
-                            } else {
-                                println!("row error");
-                            }
+                        for midi_advanced_msg in midi_elements_vec[row_range].iter() {
+                            self.element_ui(ui, midi_advanced_msg);
                         }
                     });
                 ui.ctx().request_repaint();
@@ -228,21 +1158,16 @@ impl eframe::App for MidiElementsGui {
         });
         // show list of midi events of selected midi function
         egui::SidePanel::right("midi function with ids events").show(ctx, |ui| {
-            if let Some(ref midi_function) = self.selected_midi_function {
-                if let Some(ref mut selected_midi_events) =
-                    self.midi_functions_with_elements_ids.get_mut(midi_function)
-                {
-                    let mut index_to_remove: Option<usize> = None;
-                    for (idx, event) in selected_midi_events.iter().enumerate() {
-                        if ui.button(format!("{}", *event)).clicked() {
-                            index_to_remove = Some(idx);
-                        }
-                    }
-                    if let Some(index_to_remove) = index_to_remove {
-                        selected_midi_events.remove(index_to_remove);
-                    }
+            self.bindings_ui(ui);
+            self.conflicts_ui(ui);
+        });
+        // a binding dropped outside of the functions and its list is removed
+        if ctx.input(|input| input.pointer.any_released()) {
+            if let Some(DragPayload::Binding { midi_function, idx }) = self.dragged.take() {
+                if let Some(unbind) = self.unbind(&midi_function, idx) {
+                    self.edit(unbind);
                 }
             }
-        });
+        }
     }
 }
@@ -0,0 +1,155 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use clap::{Parser, Subcommand, ValueEnum};
+use midi_mapper::{
+    file_format::{parse_file, write_file},
+    mapping_validation::{
+        controller_profile_schema, find_file_binding_conflicts, midi_function_file_schema,
+        midi_function_with_elements_file_schema, midi_mapping_file_schema, validate_file,
+        MappingFileKind,
+    },
+    midi_function::{parse_file_to_midi_functions, MidiFunctionFile},
+    midi_mapping::{parse_file_to_midi_mapping, write_midi_mapping_to_file, MidiMappingFile},
+};
+use std::process::exit;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// convert a functions or mapping file between json, toml and yaml (detected by extension)
+    Convert {
+        /// input filepath
+        #[arg(value_name = "filepath")]
+        input: String,
+        /// output filepath
+        #[arg(value_name = "filepath")]
+        output: String,
+        /// read the input as mapping file and migrate it to the current version
+        #[arg(short, long)]
+        migrate: bool,
+    },
+    /// print the json schema of a file kind
+    Schema {
+        #[arg(value_enum)]
+        kind: SchemaKind,
+        /// write the schema to a file instead of stdout
+        #[arg(short, long, value_name = "filepath")]
+        output: Option<String>,
+    },
+    /// check a functions or mapping file against its schema and the semantic rules,
+    /// overlapping bindings of a mapping file are reported as warnings
+    Validate {
+        /// filepath of the file to check
+        #[arg(value_name = "filepath")]
+        input: String,
+        /// midi_functions_filepath, bound function names have to be part of it
+        #[arg(short = 'f', long, value_name = "filepath")]
+        midi_functions_filepath: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SchemaKind {
+    Functions,
+    Elements,
+    Mapping,
+    Profile,
+}
+
+fn convert(input: &str, output: &str, migrate: bool) -> Result<(), String> {
+    if migrate {
+        let midi_mapping = parse_file_to_midi_mapping(input)?;
+        write_midi_mapping_to_file(output, &midi_mapping)
+    } else {
+        let value: serde_json::Value = parse_file(input)?;
+        // through the typed files, so the fields keep their order
+        match MappingFileKind::detect(&value) {
+            MappingFileKind::Mapping => {
+                write_midi_mapping_to_file(output, &MidiMappingFile::from_json_value(value)?)
+            }
+            MappingFileKind::Functions => {
+                let midi_functions: MidiFunctionFile = serde_json::from_value(value)
+                    .map_err(|err| format!("error in json deserialize {}", err))?;
+                write_file(output, &midi_functions)
+            }
+            // maps of function names, sorted by name like in the mapping files
+            MappingFileKind::FunctionsWithElements | MappingFileKind::LegacyMapping => {
+                write_file(output, &value)
+            }
+        }
+    }
+}
+
+fn schema(kind: SchemaKind, output: Option<String>) -> Result<(), String> {
+    let schema = match kind {
+        SchemaKind::Functions => midi_function_file_schema(),
+        SchemaKind::Elements => midi_function_with_elements_file_schema(),
+        SchemaKind::Mapping => midi_mapping_file_schema(),
+        SchemaKind::Profile => controller_profile_schema(),
+    };
+    match output {
+        Some(output) => write_file(&output, &schema),
+        None => {
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+            Ok(())
+        }
+    }
+}
+
+fn validate(input: &str, midi_functions_filepath: Option<String>) -> Result<(), String> {
+    let midi_functions = midi_functions_filepath
+        .map(|filepath| parse_file_to_midi_functions(&filepath))
+        .transpose()?;
+    let issues = validate_file(input, midi_functions.as_ref())?;
+    for issue in issues.iter() {
+        println!("{}: {}", input, issue);
+    }
+    if !issues.is_empty() {
+        return Err(format!("{} problem(s) found in {}", issues.len(), input));
+    }
+    // overlapping bindings are allowed, they are only reported
+    for conflict in find_file_binding_conflicts(input)? {
+        println!("{}: warning: {}", input, conflict);
+    }
+    Ok(())
+}
+
+fn main() {
+    let result = match Args::parse().command {
+        Command::Convert {
+            input,
+            output,
+            migrate,
+        } => convert(&input, &output, migrate),
+        Command::Schema { kind, output } => schema(kind, output),
+        Command::Validate {
+            input,
+            midi_functions_filepath,
+        } => validate(&input, midi_functions_filepath),
+    };
+    if let Err(err) = result {
+        println!("{err}");
+        exit(1);
+    }
+}
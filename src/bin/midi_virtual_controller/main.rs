@@ -0,0 +1,103 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use clap::Parser;
+use crossbeam_channel::unbounded;
+use eframe::{self, egui::ViewportBuilder};
+use midi_mapper::{
+    controller_profile::{parse_file_to_controller_profile, ControllerProfile},
+    jackprocess::{start_jack_output_thread, AutoConnect, JackConnectionManager},
+    midi_mapping::parse_file_to_midi_mapping,
+};
+use std::sync::{atomic::AtomicUsize, Arc};
+mod virtual_control;
+mod virtual_controller_gui;
+use virtual_control::virtual_controls;
+use virtual_controller_gui::VirtualControllerGui;
+
+/// name of the output port if none is given
+const DEFAULT_OUTPUT_PORT: &str = "midi_out";
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// controller profile with the controls to show, can be given multiple times
+    #[arg(long = "profile", value_name = "filepath")]
+    pub profiles: Vec<String>,
+    /// mapping file, its bindings not found in a profile are shown as controls
+    #[arg(short, long, value_name = "filepath")]
+    pub midi_mapping_filepath: Option<String>,
+    /// name of the output port (default: midi_out)
+    #[arg(short, long, value_name = "name")]
+    pub output_port: Option<String>,
+    /// connect the output to jack midi inputs matching the regex, can be given multiple times
+    #[arg(short, long = "connect", value_name = "regex")]
+    pub connects: Vec<AutoConnect>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let controller_profiles: Vec<ControllerProfile> = args
+        .profiles
+        .iter()
+        .filter_map(|filepath| {
+            parse_file_to_controller_profile(filepath)
+                .map_err(|err| println!("{err}"))
+                .ok()
+        })
+        .collect();
+    let midi_mapping = args.midi_mapping_filepath.map(|filepath| {
+        parse_file_to_midi_mapping(&filepath).unwrap_or_else(|err| {
+            println!("{err}");
+            std::process::exit(1);
+        })
+    });
+    let (tx_midi_out, rx_midi_out) = unbounded();
+    let (tx_close, rx_close) = unbounded();
+    let (connection_manager, jack_graph_updater) = JackConnectionManager::new();
+    let failed_writes = Arc::new(AtomicUsize::new(0));
+    let jack_midi_thread = start_jack_output_thread(
+        rx_close,
+        rx_midi_out,
+        "virtual_controller".to_string(),
+        args.output_port
+            .unwrap_or_else(|| DEFAULT_OUTPUT_PORT.to_string()),
+        args.connects,
+        Some(jack_graph_updater),
+        failed_writes.clone(),
+    );
+    let virtual_controller_gui = VirtualControllerGui {
+        midi_thread: Some(jack_midi_thread),
+        tx_close: Some(tx_close),
+        tx_midi_out: Some(tx_midi_out),
+        virtual_controls: virtual_controls(&controller_profiles, midi_mapping.as_ref()),
+        connection_manager: Some(connection_manager),
+        failed_writes,
+        ..Default::default()
+    };
+
+    let options = eframe::NativeOptions {
+        viewport: ViewportBuilder::default().with_inner_size([600.0, 600.0]),
+        ..Default::default()
+    };
+
+    let _ = eframe::run_native(
+        "VirtualController",
+        options,
+        Box::new(|_cc| Box::new(virtual_controller_gui)),
+    );
+}
@@ -0,0 +1,121 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use midi_mapper::{
+    controller_profile::{ControlKind, ControllerProfile},
+    midi_mapping::{BindingControl, BindingMode, MidiBinding, MidiMappingFile},
+};
+
+/// A control of the virtual controller and its current state
+pub struct VirtualControl {
+    pub label: String,
+    pub binding: MidiBinding,
+    pub kind: ControlKind,
+    pub value: u16,
+    /// turned steps of an encoder or jog wheel
+    pub steps: i32,
+}
+
+impl VirtualControl {
+    pub fn new(label: String, mut binding: MidiBinding, kind: ControlKind) -> Self {
+        // encoders and buttons of a profile have no mode
+        if binding.mode.is_none() {
+            binding.mode = match kind {
+                ControlKind::Encoder | ControlKind::Jog => {
+                    Some(BindingMode::RelativeTwosComplement)
+                }
+                ControlKind::Button | ControlKind::Pad => Some(BindingMode::Momentary),
+                ControlKind::Fader | ControlKind::Knob => None,
+            };
+        }
+        // pitch bend rests in the center
+        let value = match binding.control {
+            BindingControl::PitchBend { .. } => 8192,
+            _ => 0,
+        };
+        VirtualControl {
+            label,
+            binding,
+            kind,
+            value,
+            steps: 0,
+        }
+    }
+
+    pub fn is_14_bit(&self) -> bool {
+        matches!(
+            self.binding.control,
+            BindingControl::Cc14 { .. } | BindingControl::PitchBend { .. }
+        )
+    }
+}
+
+/// Kind of widget for a binding without a profile
+pub fn binding_control_kind(binding: &MidiBinding) -> ControlKind {
+    match (binding.control, binding.mode) {
+        (_, Some(BindingMode::RelativeTwosComplement | BindingMode::RelativeOffset)) => {
+            ControlKind::Encoder
+        }
+        (BindingControl::Note { .. }, _) => ControlKind::Pad,
+        (_, Some(BindingMode::Momentary)) => ControlKind::Button,
+        (BindingControl::Cc { .. }, _) => ControlKind::Knob,
+        (BindingControl::Cc14 { .. } | BindingControl::PitchBend { .. }, _) => ControlKind::Fader,
+    }
+}
+
+/// The controls of the profiles, then the bindings of the mapping not found in a profile
+pub fn virtual_controls(
+    controller_profiles: &[ControllerProfile],
+    midi_mapping: Option<&MidiMappingFile>,
+) -> Vec<VirtualControl> {
+    let mut virtual_controls: Vec<VirtualControl> = Vec::new();
+    for controller_profile in controller_profiles {
+        for control in controller_profile.controls.iter() {
+            let binding = MidiBinding::from_address(&control.address);
+            let kind = control
+                .kind
+                .unwrap_or_else(|| binding_control_kind(&binding));
+            virtual_controls.push(VirtualControl::new(control.name.clone(), binding, kind));
+        }
+    }
+    let Some(midi_mapping) = midi_mapping else {
+        return virtual_controls;
+    };
+    for (midi_function, bindings) in midi_mapping.midi_functions.iter() {
+        for (idx, binding) in bindings.iter().enumerate() {
+            // the port is the one of the receiving input, all controls are sent on the same output
+            let known = virtual_controls.iter().any(|virtual_control| {
+                virtual_control.binding.control == binding.control
+                    && virtual_control.binding.device == binding.device
+            });
+            if known {
+                continue;
+            }
+            let label = if bindings.len() > 1 {
+                format!("{} {}", midi_function, idx + 1)
+            } else {
+                midi_function.clone()
+            };
+            virtual_controls.push(VirtualControl::new(
+                label,
+                binding.clone(),
+                binding_control_kind(binding),
+            ));
+        }
+    }
+    virtual_controls
+}
@@ -0,0 +1,225 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::virtual_control::VirtualControl;
+use eframe::egui::{self, ScrollArea, ViewportCommand};
+use midi_mapper::{
+    controller_profile::ControlKind,
+    jack_connection_panel::jack_connection_panel,
+    jackprocess::JackConnectionManager,
+    midi_egui_elements::{
+        midi_encoder, midi_fader, midi_knob, midi_pad_grid, FaderOrientation, MidiPad,
+        MidiResolution, PadEvent,
+    },
+    midi_mapping::{BindingControl, BindingMode},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+const PAD_COLUMNS: usize = 8;
+
+#[derive(Default)]
+pub struct VirtualControllerGui {
+    pub midi_thread: Option<std::thread::JoinHandle<()>>,
+    pub tx_close: Option<crossbeam_channel::Sender<bool>>,
+    pub tx_midi_out: Option<crossbeam_channel::Sender<Vec<u8>>>,
+    pub virtual_controls: Vec<VirtualControl>,
+    pub connection_manager: Option<JackConnectionManager>,
+    pub show_connections: bool,
+    pub last_sent: Vec<Vec<u8>>,
+    /// messages the jack thread could not write to the port
+    pub failed_writes: Arc<AtomicUsize>,
+}
+
+impl VirtualControllerGui {
+    fn send(&mut self, midi_messages: Vec<Vec<u8>>) {
+        if let Some(ref tx_midi_out) = self.tx_midi_out {
+            for midi_message in midi_messages.iter() {
+                let _ = tx_midi_out.send(midi_message.clone());
+            }
+        }
+        self.last_sent = midi_messages;
+    }
+
+    /// Faders, knobs, encoders and jog wheels
+    fn continuous_controls_ui(&mut self, ui: &mut egui::Ui) {
+        let mut sent = Vec::new();
+        ui.horizontal_wrapped(|ui| {
+            for virtual_control in self.virtual_controls.iter_mut() {
+                let resolution = if virtual_control.is_14_bit() {
+                    MidiResolution::FourteenBit
+                } else {
+                    MidiResolution::SevenBit
+                };
+                let label = virtual_control.label.as_str();
+                match virtual_control.kind {
+                    ControlKind::Fader => {
+                        let orientation = match virtual_control.binding.control {
+                            BindingControl::PitchBend { .. } => FaderOrientation::Horizontal,
+                            _ => FaderOrientation::Vertical,
+                        };
+                        let response = ui.add(midi_fader(
+                            label,
+                            &mut virtual_control.value,
+                            resolution,
+                            orientation,
+                        ));
+                        if response.changed() {
+                            sent.extend(
+                                virtual_control.binding.midi_messages(virtual_control.value),
+                            );
+                        }
+                    }
+                    ControlKind::Knob => {
+                        let response =
+                            ui.add(midi_knob(label, &mut virtual_control.value, resolution));
+                        if response.changed() {
+                            sent.extend(
+                                virtual_control.binding.midi_messages(virtual_control.value),
+                            );
+                        }
+                    }
+                    ControlKind::Encoder | ControlKind::Jog => {
+                        let last_steps = virtual_control.steps;
+                        let response = ui.add(midi_encoder(
+                            label,
+                            &mut virtual_control.steps,
+                            MidiResolution::SevenBit,
+                        ));
+                        if response.changed() {
+                            let delta = (virtual_control.steps - last_steps).clamp(-64, 63) as i8;
+                            let mode = virtual_control
+                                .binding
+                                .mode
+                                .unwrap_or(BindingMode::RelativeTwosComplement);
+                            if let Some(value) = mode.relative_value(delta) {
+                                sent.extend(virtual_control.binding.midi_messages(value as u16));
+                            }
+                        }
+                    }
+                    ControlKind::Pad | ControlKind::Button => {}
+                }
+            }
+        });
+        if !sent.is_empty() {
+            self.send(sent);
+        }
+    }
+
+    /// Pads and buttons, pressed ones are lit with the sent velocity
+    fn pads_ui(&mut self, ui: &mut egui::Ui) {
+        let pad_idxs: Vec<usize> = self
+            .virtual_controls
+            .iter()
+            .enumerate()
+            .filter(|(_, virtual_control)| {
+                matches!(virtual_control.kind, ControlKind::Pad | ControlKind::Button)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if pad_idxs.is_empty() {
+            return;
+        }
+        let pads: Vec<MidiPad> = pad_idxs
+            .iter()
+            .map(|idx| {
+                let virtual_control = &self.virtual_controls[*idx];
+                let mut pad = MidiPad::new(virtual_control.label.clone(), None);
+                pad.velocity = virtual_control.value.min(127) as u8;
+                pad
+            })
+            .collect();
+        let pad_grid_response = midi_pad_grid(ui, "virtual pads", &pads, PAD_COLUMNS);
+        let mut sent = Vec::new();
+        for pad_event in pad_grid_response.events {
+            let (idx, value) = match pad_event {
+                PadEvent::Pressed { idx, velocity } => (idx, velocity as u16),
+                PadEvent::Released { idx } => (idx, 0),
+            };
+            let virtual_control = &mut self.virtual_controls[pad_idxs[idx]];
+            // buttons sending controller values are fully on while pressed
+            virtual_control.value = match virtual_control.binding.control {
+                BindingControl::Note { .. } => value,
+                _ if value > 0 => 127,
+                _ => 0,
+            };
+            sent.extend(virtual_control.binding.midi_messages(virtual_control.value));
+        }
+        if !sent.is_empty() {
+            self.send(sent);
+        }
+    }
+}
+
+impl eframe::App for VirtualControllerGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("control").show(ctx, |ui| {
+            ui.heading("VirtualController");
+            ui.horizontal(|ui| {
+                if ui.button("close").clicked() {
+                    if let Some(x) = &self.tx_close {
+                        x.send(false).unwrap();
+                        if let Some(midi_thread) = self.midi_thread.take() {
+                            let _ = midi_thread.join();
+                        }
+                    };
+                    ctx.send_viewport_cmd(ViewportCommand::Close)
+                }
+                if self.connection_manager.is_some() {
+                    ui.toggle_value(&mut self.show_connections, "connections");
+                }
+                let last_sent: Vec<String> = self
+                    .last_sent
+                    .iter()
+                    .map(|midi_message| {
+                        midi_message
+                            .iter()
+                            .map(|byte| format!("{:02X}", byte))
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    })
+                    .collect();
+                ui.label(format!("last sent: {}", last_sent.join(", ")));
+                let failed_writes = self.failed_writes.load(Ordering::Relaxed);
+                if failed_writes > 0 {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("{} messages could not be written", failed_writes),
+                    );
+                }
+            });
+        });
+        if let Some(ref connection_manager) = self.connection_manager {
+            egui::Window::new("jack connections")
+                .open(&mut self.show_connections)
+                .show(ctx, |ui| jack_connection_panel(ui, connection_manager));
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.virtual_controls.is_empty() {
+                ui.label("no controls, give a controller profile or a mapping file");
+                return;
+            }
+            ScrollArea::vertical().show(ui, |ui| {
+                self.continuous_controls_ui(ui);
+                ui.separator();
+                self.pads_ui(ui);
+            });
+        });
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::midi_mapping::MidiBinding;
+use std::fmt;
+
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+pub enum ConflictKind {
+    /// the same control is bound to several functions, all of them are called
+    SharedControl,
+    /// a 14 bit pair and a single controller of it, or two pairs sharing a controller
+    Overlapping14Bit,
+    /// a binding for all ports and one for a single port of the same control,
+    /// on that port only the port binding is called
+    ShadowedPort,
+}
+
+/// A binding of a function
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct BindingRef {
+    pub midi_function: String,
+    pub binding: MidiBinding,
+}
+
+/// Two bindings listening to the same messages, `first` sorts before `second`
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct BindingConflict {
+    pub kind: ConflictKind,
+    pub first: BindingRef,
+    pub second: BindingRef,
+}
+
+impl BindingConflict {
+    pub fn involves(&self, midi_function: &str) -> bool {
+        self.first.midi_function == midi_function || self.second.midi_function == midi_function
+    }
+}
+
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = self.first.binding.without_mode();
+        let second = self.second.binding.without_mode();
+        match self.kind {
+            ConflictKind::SharedControl => write!(
+                f,
+                "{} is bound to {} and {}",
+                first, self.first.midi_function, self.second.midi_function
+            ),
+            ConflictKind::Overlapping14Bit => write!(
+                f,
+                "{} of {} overlaps {} of {}",
+                first, self.first.midi_function, second, self.second.midi_function
+            ),
+            ConflictKind::ShadowedPort => {
+                let (port_ref, any_port_ref) = if self.first.binding.port.is_some() {
+                    (&self.first, &self.second)
+                } else {
+                    (&self.second, &self.first)
+                };
+                write!(
+                    f,
+                    "{} of {} hides {} of {} on port {}",
+                    port_ref.binding.without_mode(),
+                    port_ref.midi_function,
+                    any_port_ref.binding.without_mode(),
+                    any_port_ref.midi_function,
+                    port_ref.binding.port.as_deref().unwrap_or_default()
+                )
+            }
+        }
+    }
+}
+
+/// How two bindings overlap, bindings differing only in `mode` are duplicates and no conflict
+fn conflict_kind(binding0: &MidiBinding, binding1: &MidiBinding) -> Option<ConflictKind> {
+    if binding0.device != binding1.device {
+        return None;
+    }
+    let same_port = binding0.port == binding1.port;
+    if !same_port && binding0.port.is_some() && binding1.port.is_some() {
+        return None;
+    }
+    let addresses1 = binding1.addresses();
+    let shared = binding0.addresses().iter().any(|address0| {
+        addresses1.iter().any(|address1| {
+            (address0.kind, address0.channel, address0.number)
+                == (address1.kind, address1.channel, address1.number)
+        })
+    });
+    if !shared {
+        None
+    } else if binding0.control != binding1.control {
+        Some(ConflictKind::Overlapping14Bit)
+    } else if !same_port {
+        Some(ConflictKind::ShadowedPort)
+    } else {
+        Some(ConflictKind::SharedControl)
+    }
+}
+
+/// All pairs of bindings which overlap, within a function and across functions.
+/// Duplicates within a function are left to the validation.
+pub fn find_binding_conflicts<'a, I>(midi_functions: I) -> Vec<BindingConflict>
+where
+    I: IntoIterator<Item = (&'a String, &'a Vec<MidiBinding>)>,
+{
+    let mut binding_refs: Vec<BindingRef> = midi_functions
+        .into_iter()
+        .flat_map(|(name, bindings)| {
+            bindings.iter().map(|binding| BindingRef {
+                midi_function: name.clone(),
+                binding: binding.clone(),
+            })
+        })
+        .collect();
+    binding_refs.sort();
+    let mut conflicts = Vec::new();
+    for (idx, first) in binding_refs.iter().enumerate() {
+        for second in binding_refs[idx + 1..].iter() {
+            let Some(kind) = conflict_kind(&first.binding, &second.binding) else {
+                continue;
+            };
+            if kind == ConflictKind::SharedControl && first.midi_function == second.midi_function {
+                continue;
+            }
+            conflicts.push(BindingConflict {
+                kind,
+                first: first.clone(),
+                second: second.clone(),
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_mapping::{BindingControl, BindingMode};
+    use std::collections::BTreeMap;
+
+    fn binding(control: BindingControl) -> MidiBinding {
+        MidiBinding {
+            control,
+            port: None,
+            device: None,
+            mode: None,
+        }
+    }
+
+    fn cc(channel: u8, controller: u8) -> MidiBinding {
+        binding(BindingControl::Cc {
+            channel,
+            controller,
+        })
+    }
+
+    fn cc14(channel: u8, msb_controller: u8, lsb_controller: u8) -> MidiBinding {
+        binding(BindingControl::Cc14 {
+            channel,
+            msb_controller,
+            lsb_controller,
+        })
+    }
+
+    fn with_port(mut binding: MidiBinding, port: &str) -> MidiBinding {
+        binding.port = Some(port.to_string());
+        binding
+    }
+
+    fn conflicts_of(
+        midi_functions: &[(&str, Vec<MidiBinding>)],
+    ) -> Vec<(ConflictKind, String, String)> {
+        let midi_functions: BTreeMap<String, Vec<MidiBinding>> = midi_functions
+            .iter()
+            .map(|(name, bindings)| (name.to_string(), bindings.clone()))
+            .collect();
+        find_binding_conflicts(&midi_functions)
+            .into_iter()
+            .map(|conflict| {
+                (
+                    conflict.kind,
+                    conflict.first.midi_function,
+                    conflict.second.midi_function,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shared_control() {
+        let mut relative = cc(1, 7);
+        relative.mode = Some(BindingMode::RelativeOffset);
+        let conflicts = conflicts_of(&[
+            // a duplicate within a function is no conflict
+            ("Volume", vec![cc(1, 7), relative]),
+            ("Pan", vec![cc(1, 7), cc(1, 10)]),
+            ("Expression", vec![cc(2, 7)]),
+        ]);
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    ConflictKind::SharedControl,
+                    "Pan".to_string(),
+                    "Volume".to_string()
+                ),
+                (
+                    ConflictKind::SharedControl,
+                    "Pan".to_string(),
+                    "Volume".to_string()
+                ),
+            ]
+        );
+
+        let conflict = &find_binding_conflicts(&BTreeMap::from([
+            ("Volume".to_string(), vec![cc(1, 7)]),
+            ("Pan".to_string(), vec![cc(1, 7)]),
+        ]))[0];
+        assert!(conflict.involves("Volume"));
+        assert!(!conflict.involves("Expression"));
+    }
+
+    #[test]
+    fn overlapping_14_bit() {
+        let conflicts = conflicts_of(&[
+            ("Cutoff", vec![cc14(1, 1, 33)]),
+            ("Modulation", vec![cc(1, 1)]),
+            ("Resonance", vec![cc14(1, 2, 33)]),
+            ("Breath", vec![cc14(2, 1, 33)]),
+        ]);
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    ConflictKind::Overlapping14Bit,
+                    "Cutoff".to_string(),
+                    "Modulation".to_string()
+                ),
+                (
+                    ConflictKind::Overlapping14Bit,
+                    "Cutoff".to_string(),
+                    "Resonance".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowed_port() {
+        let conflicts = conflicts_of(&[
+            ("Volume", vec![with_port(cc(1, 7), "a")]),
+            ("Gain", vec![with_port(cc(1, 7), "b")]),
+            ("Pan", vec![cc(1, 7)]),
+        ]);
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    ConflictKind::ShadowedPort,
+                    "Gain".to_string(),
+                    "Pan".to_string()
+                ),
+                (
+                    ConflictKind::ShadowedPort,
+                    "Pan".to_string(),
+                    "Volume".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject, StringValidation},
+    JsonSchema,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressKind {
+    Note,
+    ControlChange,
+    PitchBend,
+}
+
+/// Identifies a single control of a midi device.
+/// `channel` is the raw channel 0..15, it is shown as 1..16 (`ch1:cc7`).
+/// `number` is the note or controller number and 0 for pitch bend.
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct ControlAddress {
+    pub kind: AddressKind,
+    pub channel: u8,
+    pub number: u8,
+    pub port: Option<String>,
+    pub device: Option<String>,
+}
+
+impl ControlAddress {
+    pub fn new(kind: AddressKind, channel: u8, number: u8) -> Self {
+        ControlAddress {
+            kind,
+            channel: channel & 0x0f,
+            number: number & 0x7f,
+            port: None,
+            device: None,
+        }
+    }
+
+    pub fn note(channel: u8, note: u8) -> Self {
+        Self::new(AddressKind::Note, channel, note)
+    }
+
+    pub fn control_change(channel: u8, controller: u8) -> Self {
+        Self::new(AddressKind::ControlChange, channel, controller)
+    }
+
+    pub fn pitch_bend(channel: u8) -> Self {
+        Self::new(AddressKind::PitchBend, channel, 0)
+    }
+
+    pub fn with_port(mut self, port: Option<String>) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn with_device(mut self, device: Option<String>) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Decode the packed ids (`0xB000 + channel<<8 + cc`) used by older mapping files
+    pub fn from_legacy_id(id: u16) -> Option<Self> {
+        let status = (id >> 12) as u8;
+        let channel = ((id >> 8) & 0x0f) as u8;
+        let number = (id & 0x7f) as u8;
+        match status {
+            0x08 | 0x09 => Some(Self::note(channel, number)),
+            0x0b => Some(Self::control_change(channel, number)),
+            0x0e => Some(Self::pitch_bend(channel)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ControlAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ch{}:", self.channel + 1)?;
+        match self.kind {
+            AddressKind::Note => write!(f, "note{}", self.number)?,
+            AddressKind::ControlChange => write!(f, "cc{}", self.number)?,
+            AddressKind::PitchBend => write!(f, "pb")?,
+        }
+        if let Some(ref port) = self.port {
+            write!(f, "@{}", port)?;
+        }
+        if let Some(ref device) = self.device {
+            write!(f, "#{}", device)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ControlAddress {
+    type Err = String;
+
+    /// Parse `ch2:cc74`, `ch1:note60` or `ch1:pb`,
+    /// optionally followed by `@port` and `#device`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid control address {}", s);
+        let (rest, device) = match s.split_once('#') {
+            Some((rest, device)) => (rest, Some(device.to_string())),
+            None => (s, None),
+        };
+        let (rest, port) = match rest.split_once('@') {
+            Some((rest, port)) => (rest, Some(port.to_string())),
+            None => (rest, None),
+        };
+        let (channel, control) = rest.split_once(':').ok_or_else(err)?;
+        let channel: u8 = channel
+            .strip_prefix("ch")
+            .and_then(|channel| channel.parse().ok())
+            .filter(|channel| (1..=16).contains(channel))
+            .ok_or_else(err)?;
+        let parse_number = |number: &str| -> Result<u8, String> {
+            number
+                .parse()
+                .ok()
+                .filter(|number| *number < 128)
+                .ok_or_else(err)
+        };
+        let address = if let Some(note) = control.strip_prefix("note") {
+            Self::note(channel - 1, parse_number(note)?)
+        } else if let Some(controller) = control.strip_prefix("cc") {
+            Self::control_change(channel - 1, parse_number(controller)?)
+        } else if control == "pb" {
+            Self::pitch_bend(channel - 1)
+        } else {
+            return Err(err());
+        };
+        Ok(address.with_port(port).with_device(device))
+    }
+}
+
+impl Serialize for ControlAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ControlAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for ControlAddress {
+    fn schema_name() -> String {
+        "ControlAddress".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let number = "([0-9]|[1-9][0-9]|1[01][0-9]|12[0-7])";
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(format!(
+                    "^ch([1-9]|1[0-6]):(note{number}|cc{number}|pb)(@[^#]+)?(#.+)?$"
+                )),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_legacy_ids() {
+        assert_eq!(
+            ControlAddress::from_legacy_id(0xb107),
+            Some(ControlAddress::control_change(1, 7))
+        );
+        // note on and note off ids are the same note
+        assert_eq!(
+            ControlAddress::from_legacy_id(0x902a),
+            Some(ControlAddress::note(0, 42))
+        );
+        assert_eq!(
+            ControlAddress::from_legacy_id(0x8f2a),
+            Some(ControlAddress::note(15, 42))
+        );
+        assert_eq!(
+            ControlAddress::from_legacy_id(0xe300),
+            Some(ControlAddress::pitch_bend(3))
+        );
+        assert_eq!(ControlAddress::from_legacy_id(0x04d2), None);
+        assert_eq!(ControlAddress::from_legacy_id(0xc105), None);
+    }
+
+    #[test]
+    fn parse_displayed_address() {
+        let address = ControlAddress::control_change(15, 127).with_port(Some("deck_a".to_string()));
+        assert_eq!(address.to_string().parse(), Ok(address));
+        assert!("ch17:cc7".parse::<ControlAddress>().is_err());
+    }
+}
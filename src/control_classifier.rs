@@ -0,0 +1,225 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::controller_profile::ControlKind;
+use crate::jackmidi::MidiMsgAdvanced;
+use crate::midi_mapping::BindingMode;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+const MAX_VALUES: usize = 32;
+// encoders send small steps, faster turns send bigger ones
+const MAX_ENCODER_STEP: u8 = 15;
+
+/// Kind of a control guessed from the messages it sent
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ControlGuess {
+    /// note on/off pairs
+    ButtonOrPad,
+    /// control change with only the values 0 and 127
+    Button,
+    /// relative values around 0 (1/127) or around 64 (63/65)
+    Encoder(BindingMode),
+    FaderOrKnob,
+    /// paired MSB/LSB controllers or pitch bend
+    FaderOrKnob14Bit,
+}
+
+impl ControlGuess {
+    pub fn binding_mode(&self) -> BindingMode {
+        match self {
+            ControlGuess::ButtonOrPad | ControlGuess::Button => BindingMode::Momentary,
+            ControlGuess::Encoder(binding_mode) => *binding_mode,
+            ControlGuess::FaderOrKnob | ControlGuess::FaderOrKnob14Bit => BindingMode::Absolute,
+        }
+    }
+
+    /// Kind to pre-fill a controller profile, faders and knobs send the same messages
+    pub fn control_kind(&self) -> ControlKind {
+        match self {
+            ControlGuess::ButtonOrPad => ControlKind::Pad,
+            ControlGuess::Button => ControlKind::Button,
+            ControlGuess::Encoder(_) => ControlKind::Encoder,
+            ControlGuess::FaderOrKnob | ControlGuess::FaderOrKnob14Bit => ControlKind::Fader,
+        }
+    }
+}
+
+impl fmt::Display for ControlGuess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlGuess::ButtonOrPad => write!(f, "button/pad"),
+            ControlGuess::Button => write!(f, "button"),
+            ControlGuess::Encoder(BindingMode::RelativeOffset) => write!(f, "encoder 63/65"),
+            ControlGuess::Encoder(_) => write!(f, "encoder 1/127"),
+            ControlGuess::FaderOrKnob => write!(f, "fader/knob"),
+            ControlGuess::FaderOrKnob14Bit => write!(f, "fader/knob 14 bit"),
+        }
+    }
+}
+
+/// Watches the messages of a single control and guesses its kind
+#[derive(Default, Clone, Debug)]
+pub struct ControlClassifier {
+    note_ons: usize,
+    note_offs: usize,
+    fourteen_bit: bool,
+    // last 7 bit values of a control change
+    values: VecDeque<u8>,
+}
+
+impl ControlClassifier {
+    pub fn push(&mut self, midi_msg: &MidiMsgAdvanced) {
+        match midi_msg {
+            MidiMsgAdvanced::MidiEmpty => {}
+            // note on with velocity 0 is a note off
+            MidiMsgAdvanced::MidiNoteOnOff(_, true, intensity) if *intensity > 0 => {
+                self.note_ons += 1
+            }
+            MidiMsgAdvanced::MidiNoteOnOff(_, _, _) => self.note_offs += 1,
+            MidiMsgAdvanced::MidiControlIdValue(_, value) => {
+                if self.values.len() == MAX_VALUES {
+                    self.values.pop_front();
+                }
+                self.values.push_back(*value as u8 & 0x7f);
+            }
+            MidiMsgAdvanced::MidiControl2IdsValue(_, _, _) => self.fourteen_bit = true,
+        }
+    }
+
+    pub fn guess(&self) -> Option<ControlGuess> {
+        if self.note_ons > 0 && self.note_offs > 0 {
+            return Some(ControlGuess::ButtonOrPad);
+        }
+        if self.fourteen_bit {
+            return Some(ControlGuess::FaderOrKnob14Bit);
+        }
+        if self.values.len() < 2 {
+            return None;
+        }
+        if self.values.iter().all(|value| *value == 0 || *value == 127) {
+            return Some(ControlGuess::Button);
+        }
+        let distinct_values: HashSet<&u8> = self.values.iter().collect();
+        // a sweep hardly repeats values, an encoder repeats its step size
+        let repeating = self.values.len() >= 3 && distinct_values.len() * 2 <= self.values.len();
+        if repeating {
+            // center itself means no movement and is not sent
+            let around = |center: u8| {
+                self.values.iter().all(|value| {
+                    let offset = value.wrapping_sub(center).wrapping_add(MAX_ENCODER_STEP) & 0x7f;
+                    *value != center && offset <= 2 * MAX_ENCODER_STEP
+                })
+            };
+            if around(0) {
+                return Some(ControlGuess::Encoder(BindingMode::RelativeTwosComplement));
+            }
+            if around(64) {
+                return Some(ControlGuess::Encoder(BindingMode::RelativeOffset));
+            }
+        }
+        if distinct_values.len() >= 3 {
+            Some(ControlGuess::FaderOrKnob)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_address::ControlAddress;
+
+    fn classify(midi_msgs: &[MidiMsgAdvanced]) -> Option<ControlGuess> {
+        let mut control_classifier = ControlClassifier::default();
+        for midi_msg in midi_msgs {
+            control_classifier.push(midi_msg);
+        }
+        control_classifier.guess()
+    }
+
+    fn control_changes(values: &[u16]) -> Vec<MidiMsgAdvanced> {
+        values
+            .iter()
+            .map(|value| {
+                MidiMsgAdvanced::MidiControlIdValue(ControlAddress::control_change(0, 16), *value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn guess_button_or_pad_from_notes() {
+        let note_on = MidiMsgAdvanced::MidiNoteOnOff(ControlAddress::note(0, 36), true, 100);
+        let note_off = MidiMsgAdvanced::MidiNoteOnOff(ControlAddress::note(0, 36), false, 0);
+        // note on with velocity 0 is a note off
+        let note_on_zero = MidiMsgAdvanced::MidiNoteOnOff(ControlAddress::note(0, 36), true, 0);
+        assert_eq!(classify(std::slice::from_ref(&note_on)), None);
+        assert_eq!(
+            classify(&[note_on.clone(), note_off]),
+            Some(ControlGuess::ButtonOrPad)
+        );
+        let guess = classify(&[note_on, note_on_zero]);
+        assert_eq!(guess, Some(ControlGuess::ButtonOrPad));
+        assert_eq!(guess.unwrap().control_kind(), ControlKind::Pad);
+    }
+
+    #[test]
+    fn guess_button_from_control_changes() {
+        let guess = classify(&control_changes(&[127, 0, 127, 0]));
+        assert_eq!(guess, Some(ControlGuess::Button));
+        assert_eq!(guess.unwrap().binding_mode(), BindingMode::Momentary);
+    }
+
+    #[test]
+    fn guess_fader_or_knob() {
+        assert_eq!(classify(&control_changes(&[10])), None);
+        assert_eq!(classify(&control_changes(&[10, 20])), None);
+        let guess = classify(&control_changes(&[10, 20, 30, 40]));
+        assert_eq!(guess, Some(ControlGuess::FaderOrKnob));
+        assert_eq!(guess.unwrap().binding_mode(), BindingMode::Absolute);
+
+        let fourteen_bit = MidiMsgAdvanced::MidiControl2IdsValue(
+            ControlAddress::control_change(0, 1),
+            ControlAddress::control_change(0, 33),
+            8192,
+        );
+        assert_eq!(
+            classify(&[fourteen_bit]),
+            Some(ControlGuess::FaderOrKnob14Bit)
+        );
+    }
+
+    #[test]
+    fn guess_encoders() {
+        let guess = classify(&control_changes(&[1, 1, 1, 127, 127, 2]));
+        assert_eq!(
+            guess,
+            Some(ControlGuess::Encoder(BindingMode::RelativeTwosComplement))
+        );
+        assert_eq!(guess.unwrap().control_kind(), ControlKind::Encoder);
+        assert_eq!(
+            classify(&control_changes(&[65, 65, 65, 63, 63])),
+            Some(ControlGuess::Encoder(BindingMode::RelativeOffset))
+        );
+        // a slow fader sweep repeats values too, but is not around a center
+        assert_eq!(
+            classify(&control_changes(&[40, 40, 41, 41, 42, 42])),
+            Some(ControlGuess::FaderOrKnob)
+        );
+    }
+}
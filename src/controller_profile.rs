@@ -0,0 +1,193 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::control_address::ControlAddress;
+use crate::file_format::{parse_file, write_file};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(
+    Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlKind {
+    Fader,
+    Knob,
+    Encoder,
+    Pad,
+    Button,
+    Jog,
+}
+
+impl ControlKind {
+    pub const ALL: [ControlKind; 6] = [
+        ControlKind::Fader,
+        ControlKind::Knob,
+        ControlKind::Encoder,
+        ControlKind::Pad,
+        ControlKind::Button,
+        ControlKind::Jog,
+    ];
+}
+
+impl fmt::Display for ControlKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ControlKind::Fader => "fader",
+            ControlKind::Knob => "knob",
+            ControlKind::Encoder => "encoder",
+            ControlKind::Pad => "pad",
+            ControlKind::Button => "button",
+            ControlKind::Jog => "jog",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Position of a control on the device, in units of a control
+#[derive(PartialEq, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default)]
+pub struct ControlPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A named control of a device, 14 bit controls are addressed by their MSB
+#[derive(PartialEq, Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ProfileControl {
+    pub name: String,
+    pub address: ControlAddress,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ControlKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<ControlPosition>,
+}
+
+/// Names, kinds and layout of the controls of a device.
+/// With `port` the profile only applies to messages of that input port.
+#[derive(PartialEq, Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct ControllerProfile {
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    pub controls: Vec<ProfileControl>,
+}
+
+fn same_control(address0: &ControlAddress, address1: &ControlAddress) -> bool {
+    address0.kind == address1.kind
+        && address0.channel == address1.channel
+        && address0.number == address1.number
+}
+
+impl ControllerProfile {
+    pub fn applies_to(&self, address: &ControlAddress) -> bool {
+        self.port.is_none() || self.port == address.port
+    }
+
+    /// The control of the profile with the same kind, channel and number, the port
+    /// and device of the profile control are ignored
+    pub fn find_control(&self, address: &ControlAddress) -> Option<&ProfileControl> {
+        if !self.applies_to(address) {
+            return None;
+        }
+        self.controls
+            .iter()
+            .find(|control| same_control(&control.address, address))
+    }
+
+    pub fn find_control_mut(&mut self, address: &ControlAddress) -> Option<&mut ProfileControl> {
+        if !self.applies_to(address) {
+            return None;
+        }
+        self.controls
+            .iter_mut()
+            .find(|control| same_control(&control.address, address))
+    }
+
+    /// Adds a control named like its address if the profile has none for `address`
+    pub fn add_control(&mut self, address: &ControlAddress) -> &mut ProfileControl {
+        let idx = match self
+            .controls
+            .iter()
+            .position(|control| same_control(&control.address, address))
+        {
+            Some(idx) => idx,
+            None => {
+                let address = ControlAddress::new(address.kind, address.channel, address.number);
+                self.controls.push(ProfileControl {
+                    name: address.to_string(),
+                    address,
+                    kind: None,
+                    position: None,
+                });
+                self.controls.len() - 1
+            }
+        };
+        &mut self.controls[idx]
+    }
+
+    /// Removes the control with the same kind, channel and number
+    pub fn remove_control(&mut self, address: &ControlAddress) -> Option<ProfileControl> {
+        let idx = self
+            .controls
+            .iter()
+            .position(|control| same_control(&control.address, address))?;
+        Some(self.controls.remove(idx))
+    }
+}
+
+/// The first control of the profiles matching `address`
+pub fn find_profile_control<'a>(
+    controller_profiles: &'a [ControllerProfile],
+    address: &ControlAddress,
+) -> Option<&'a ProfileControl> {
+    controller_profiles
+        .iter()
+        .find_map(|controller_profile| controller_profile.find_control(address))
+}
+
+/// Name of the control from the profiles or the address, the port is added
+/// if the profile is not bound to a port
+pub fn control_label(
+    controller_profiles: &[ControllerProfile],
+    address: &ControlAddress,
+) -> String {
+    let found = controller_profiles.iter().find_map(|controller_profile| {
+        controller_profile
+            .find_control(address)
+            .map(|control| (controller_profile, control))
+    });
+    match (found, &address.port) {
+        (Some((controller_profile, control)), Some(port)) if controller_profile.port.is_none() => {
+            format!("{}@{}", control.name, port)
+        }
+        (Some((_, control)), _) => control.name.clone(),
+        (None, _) => address.to_string(),
+    }
+}
+
+/// Read a json, toml or yaml controller profile
+pub fn parse_file_to_controller_profile(file_path_str: &str) -> Result<ControllerProfile, String> {
+    parse_file(file_path_str)
+}
+
+pub fn write_controller_profile_to_file(
+    file_path_str: &str,
+    controller_profile: &ControllerProfile,
+) -> Result<(), String> {
+    write_file(file_path_str, controller_profile)
+}
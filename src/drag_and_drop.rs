@@ -0,0 +1,69 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use eframe::egui::{self, CursorIcon, Id, InnerResponse, LayerId, Order, Sense};
+
+/// Shows `body` as draggable item, while dragged it follows the pointer.
+/// The response is the drag response, `drag_started` tells when to remember the payload.
+/// Widgets in `body` can still be clicked.
+pub fn drag_source<R>(
+    ui: &mut egui::Ui,
+    id: Id,
+    body: impl FnOnce(&mut egui::Ui) -> R,
+) -> InnerResponse<R> {
+    if ui.memory(|memory| memory.is_being_dragged(id)) {
+        ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        let layer_id = LayerId::new(Order::Tooltip, id);
+        let InnerResponse { inner, response } = ui.with_layer_id(layer_id, body);
+        if let Some(pointer_pos) = ui.ctx().pointer_interact_pos() {
+            ui.ctx()
+                .translate_layer(layer_id, pointer_pos - response.rect.center());
+        }
+        let response = ui.interact(response.rect, id, Sense::drag());
+        InnerResponse { inner, response }
+    } else {
+        let InnerResponse { inner, response } = ui.scope(body);
+        let response = ui.interact(response.rect, id, Sense::drag());
+        if response.hovered() {
+            ui.ctx().set_cursor_icon(CursorIcon::Grab);
+        }
+        InnerResponse { inner, response }
+    }
+}
+
+/// Shows `body` and highlights it while an item it can accept is dragged over it.
+/// Returns true in the frame the item is dropped on it.
+pub fn drop_target<R>(
+    ui: &mut egui::Ui,
+    can_accept: bool,
+    body: impl FnOnce(&mut egui::Ui) -> R,
+) -> InnerResponse<bool> {
+    let response = ui.scope(body).response;
+    let hovered = can_accept && ui.rect_contains_pointer(response.rect);
+    if hovered {
+        ui.painter().rect_stroke(
+            response.rect.expand(2.0),
+            2.0,
+            ui.visuals().selection.stroke,
+        );
+    }
+    let dropped = hovered && ui.input(|input| input.pointer.any_released());
+    InnerResponse {
+        inner: dropped,
+        response,
+    }
+}
@@ -0,0 +1,93 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum FileFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl FileFormat {
+    /// Detect the format by the file extension, files without a known extension are json
+    pub fn from_path(file_path_str: &str) -> Self {
+        let extension = Path::new(file_path_str)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("toml") => FileFormat::Toml,
+            Some("yaml") | Some("yml") => FileFormat::Yaml,
+            _ => FileFormat::Json,
+        }
+    }
+
+    pub fn deserialize_str<T: DeserializeOwned>(&self, contents: &str) -> Result<T, String> {
+        match self {
+            FileFormat::Json => serde_json::from_str(contents)
+                .map_err(|err| format!("error in json deserialize {}", err)),
+            FileFormat::Toml => {
+                toml::from_str(contents).map_err(|err| format!("error in toml deserialize {}", err))
+            }
+            FileFormat::Yaml => serde_yaml::from_str(contents)
+                .map_err(|err| format!("error in yaml deserialize {}", err)),
+        }
+    }
+
+    pub fn serialize_to_string<T: Serialize>(&self, value: &T) -> Result<String, String> {
+        match self {
+            FileFormat::Json => serde_json::to_string_pretty(value)
+                .map_err(|err| format!("error in json serialize {}", err)),
+            FileFormat::Toml => toml::to_string_pretty(value)
+                .map_err(|err| format!("error in toml serialize {}", err)),
+            FileFormat::Yaml => serde_yaml::to_string(value)
+                .map_err(|err| format!("error in yaml serialize {}", err)),
+        }
+    }
+}
+
+pub fn read_file_to_string(file_path_str: &str) -> Result<String, String> {
+    let mut file_content =
+        File::open(file_path_str).map_err(|err| format!("Could not read the file {}", err))?;
+    let mut contents = String::new();
+    file_content
+        .read_to_string(&mut contents)
+        .map_err(|err| format!("Could not read file to string {}", err))?;
+    Ok(contents)
+}
+
+/// Read a json, toml or yaml file into the given serde type
+pub fn parse_file<T: DeserializeOwned>(file_path_str: &str) -> Result<T, String> {
+    let contents = read_file_to_string(file_path_str)?;
+    FileFormat::from_path(file_path_str).deserialize_str(&contents)
+}
+
+/// Write the given serde type as json, toml or yaml depending on the file extension
+pub fn write_file<T: Serialize>(file_path_str: &str, value: &T) -> Result<(), String> {
+    let mut contents = FileFormat::from_path(file_path_str).serialize_to_string(value)?;
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    let mut file =
+        File::create(file_path_str).map_err(|err| format!("Could not create the file {}", err))?;
+    file.write_all(contents.as_bytes())
+        .map_err(|err| format!("Could not write the file {}", err))
+}
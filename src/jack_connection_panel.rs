@@ -0,0 +1,102 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::jackprocess::{JackConnectionManager, JackPortInfo};
+use eframe::egui;
+
+fn short_name(name: &str) -> &str {
+    name.split_once(':')
+        .map_or(name, |(_, short_name)| short_name)
+}
+
+fn port_label(ui: &mut egui::Ui, port_info: &JackPortInfo) {
+    let response = ui.label(&port_info.name);
+    if !port_info.aliases.is_empty() {
+        response.on_hover_text(port_info.aliases.join("\n"));
+    }
+}
+
+/// Grid of `ports` against the own ports, a checkbox connects or disconnects a pair.
+/// `ports_are_sources` tells the direction of the connections.
+fn connection_grid(
+    ui: &mut egui::Ui,
+    id: &str,
+    connection_manager: &JackConnectionManager,
+    ports: &[JackPortInfo],
+    own_ports: &[JackPortInfo],
+    ports_are_sources: bool,
+) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        ui.label("");
+        for own_port in own_ports {
+            ui.label(short_name(&own_port.name));
+        }
+        ui.end_row();
+        for port_info in ports {
+            port_label(ui, port_info);
+            for own_port in own_ports {
+                let (source, destination) = if ports_are_sources {
+                    (&port_info.name, &own_port.name)
+                } else {
+                    (&own_port.name, &port_info.name)
+                };
+                let mut connected = port_info.connections.contains(&own_port.name);
+                if ui.checkbox(&mut connected, "").changed() {
+                    if connected {
+                        connection_manager.connect(source, destination);
+                    } else {
+                        connection_manager.disconnect(source, destination);
+                    }
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Lists the jack midi ports of other programs and connects them to the own ports
+pub fn jack_connection_panel(ui: &mut egui::Ui, connection_manager: &JackConnectionManager) {
+    let graph = connection_manager.graph();
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.heading("sources");
+        if graph.sources.is_empty() {
+            ui.label("no midi sources");
+        } else {
+            connection_grid(
+                ui,
+                "jack_sources",
+                connection_manager,
+                &graph.sources,
+                &graph.own_inputs,
+                true,
+            );
+        }
+        ui.heading("destinations");
+        if graph.destinations.is_empty() {
+            ui.label("no midi destinations");
+        } else {
+            connection_grid(
+                ui,
+                "jack_destinations",
+                connection_manager,
+                &graph.destinations,
+                &graph.own_outputs,
+                false,
+            );
+        }
+    });
+}
@@ -15,83 +15,57 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use crate::control_address::ControlAddress;
 use crate::util::*;
 use serde::{Deserialize, Serialize};
 use std::convert::From;
+use std::sync::Arc;
 
 const MAX_MIDI: usize = 3;
-type MidiId = u16;
-type Note = u8;
+/// Microseconds between the MSB and LSB of a 14 bit control, about 10 frames at 48 kHz
+const MAX_14BIT_PAIR_TIME_DIFF: u64 = 200;
 type Intensity = u8;
 
 #[derive(Eq, Hash, PartialEq, Serialize, Deserialize, Clone)]
 pub enum MidiMsgAdvanced {
     MidiEmpty,
-    MidiNoteOnOff(MidiId, MidiId, bool, Note, Intensity),
-    MidiControlIdValue(MidiId, u16),
-    MidiControl2IdsValue(MidiId, MidiId, u16),
+    MidiNoteOnOff(ControlAddress, bool, Intensity),
+    MidiControlIdValue(ControlAddress, u16),
+    MidiControl2IdsValue(ControlAddress, ControlAddress, u16),
 }
 
 impl MidiMsgAdvanced {
-    pub fn get_id(&self) -> u16 {
+    pub fn get_address(&self) -> Option<&ControlAddress> {
         match self {
-            Self::MidiEmpty => 0,
-            Self::MidiNoteOnOff(id0, _, _, _, _) => *id0,
-            Self::MidiControlIdValue(id, _) => *id,
-            Self::MidiControl2IdsValue(id0, _, _) => *id0,
+            Self::MidiEmpty => None,
+            Self::MidiNoteOnOff(address, _, _) => Some(address),
+            Self::MidiControlIdValue(address, _) => Some(address),
+            Self::MidiControl2IdsValue(address, _, _) => Some(address),
         }
     }
 }
 
 impl MidiMsgAdvanced {
     pub fn from_midi_msg_cc(midi_msg: MidiMsgControlChange) -> Self {
-        let midi_id = midi_msg.get_id();
         let midi_value = 128 * midi_msg.get_value();
-        MidiMsgAdvanced::MidiControlIdValue(midi_id, midi_value)
+        MidiMsgAdvanced::MidiControlIdValue(midi_msg.address(), midi_value)
     }
 
     pub fn from_midi_msg_cc2ids(
         midi_msg0: MidiMsgControlChange,
         midi_msg1: MidiMsgControlChange,
     ) -> Self {
-        let midi_id0 = midi_msg0.get_id();
-        let midi_id1 = midi_msg1.get_id();
-
         let midi_value = 128 * midi_msg0.get_value() + midi_msg1.get_value();
 
-        MidiMsgAdvanced::MidiControl2IdsValue(midi_id0, midi_id1, midi_value)
+        MidiMsgAdvanced::MidiControl2IdsValue(midi_msg0.address(), midi_msg1.address(), midi_value)
     }
 
     pub fn from_midi_msgs_note_on(midi_msg: MidiMsgNoteOn) -> Self {
-        let midi_id_on = midi_msg.get_id();
-        let midi_value = true;
-        let midi_id_off = midi_id_on - 0x1000;
-        let midi_note = midi_msg.get_data()[1];
-        let midi_intensity = midi_msg.get_data()[2];
-
-        MidiMsgAdvanced::MidiNoteOnOff(
-            midi_id_on,
-            midi_id_off,
-            midi_value,
-            midi_note,
-            midi_intensity,
-        )
+        MidiMsgAdvanced::MidiNoteOnOff(midi_msg.address(), true, midi_msg.velocity)
     }
 
     pub fn from_midi_msgs_note_off(midi_msg: MidiMsgNoteOff) -> Self {
-        let midi_id_on = midi_msg.get_id() + 0x1000;
-        let midi_value = false;
-        let midi_id_off = midi_msg.get_id();
-        let midi_note = midi_msg.get_data()[1];
-        let midi_intensity = midi_msg.get_data()[2];
-
-        MidiMsgAdvanced::MidiNoteOnOff(
-            midi_id_on,
-            midi_id_off,
-            midi_value,
-            midi_note,
-            midi_intensity,
-        )
+        MidiMsgAdvanced::MidiNoteOnOff(midi_msg.address(), false, midi_msg.velocity)
     }
 
     pub fn from_current_and_last_opt_midi_msgs(
@@ -100,54 +74,58 @@ impl MidiMsgAdvanced {
             &mut Option<Box<dyn MidiMsgBase>>,
         ),
     ) -> Option<Self> {
-        let mut id = current_midi_msg.get_id();
+        let address = current_midi_msg.get_address();
         let last_last_midi_msg = last_opt_midi_msg.take();
         let midi_msg_value = current_midi_msg.get_value();
         let midi_msgs_data = current_midi_msg.get_data();
         let midi_msg_type = current_midi_msg.type_of().to_string();
         let midi_msg_timestamp = current_midi_msg.get_time();
-        let mut id_value_time_diff_to_last_msg = None;
-        if let Some(last_last_midi_msg) = last_last_midi_msg {
+        let mut address_value_time_diff_to_last_msg = None;
+        if let (Some(last_last_midi_msg), Some(ref address)) = (last_last_midi_msg, &address) {
             let time_diff = midi_msg_timestamp.abs_diff(last_last_midi_msg.get_time());
-            let last_id = last_last_midi_msg.get_id();
             let last_value = last_last_midi_msg.get_value();
-            if time_diff < 10 && id > last_id {
-                id_value_time_diff_to_last_msg = Some((last_id, last_value, time_diff));
+            // a 14 bit control sends its MSB and then its LSB on a higher controller number
+            if let Some(last_address) = last_last_midi_msg.get_address() {
+                if time_diff < MAX_14BIT_PAIR_TIME_DIFF
+                    && last_address.kind == address.kind
+                    && last_address.channel == address.channel
+                    && last_address.port == address.port
+                    && address.number > last_address.number
+                {
+                    address_value_time_diff_to_last_msg =
+                        Some((last_address, last_value, time_diff));
+                }
             }
         }
         *last_opt_midi_msg = Some(current_midi_msg);
+        let address = address?;
         match midi_msg_type.as_str() {
             "MidiMsgControlChange" => {
-                if let Some((last_id, last_value, _time_diff)) = id_value_time_diff_to_last_msg {
+                if let Some((last_address, last_value, _time_diff)) =
+                    address_value_time_diff_to_last_msg
+                {
                     Some(MidiMsgAdvanced::MidiControl2IdsValue(
-                        last_id,
-                        id,
+                        last_address,
+                        address,
                         midi_msg_value + last_value * 128,
                     ))
                 } else {
-                    Some(MidiMsgAdvanced::MidiControlIdValue(id, midi_msg_value))
+                    Some(MidiMsgAdvanced::MidiControlIdValue(address, midi_msg_value))
                 }
             }
             "MidiMsgNoteOn" => Some(MidiMsgAdvanced::MidiNoteOnOff(
-                id,
-                id - 0x1000,
+                address,
                 true,
-                midi_msgs_data[1],
                 midi_msgs_data[2],
             )),
-            "MidiMsgNoteOff" => {
-                id += 0x1000;
-                Some(MidiMsgAdvanced::MidiNoteOnOff(
-                    id,
-                    id - 0x1000,
-                    false,
-                    midi_msgs_data[1],
-                    midi_msgs_data[2],
-                ))
-            }
+            "MidiMsgNoteOff" => Some(MidiMsgAdvanced::MidiNoteOnOff(
+                address,
+                false,
+                midi_msgs_data[2],
+            )),
             "MidiMsgPitchBend" => Some(MidiMsgAdvanced::MidiControl2IdsValue(
-                id,
-                id,
+                address.clone(),
+                address,
                 midi_msg_value,
             )),
             _ => None,
@@ -159,50 +137,39 @@ impl std::fmt::Display for MidiMsgAdvanced {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::MidiEmpty => write!(f, "MidiEmpty"),
-            Self::MidiNoteOnOff(id0, id1, value, note, intensity) => {
+            Self::MidiNoteOnOff(address, value, intensity) => {
+                write!(f, "NoteOnOff({}, {}, {})", address, *value, intensity)
+            }
+            Self::MidiControlIdValue(address, value) => {
+                write!(f, "MidiControlIdValue({}, {})", address, value)
+            }
+            Self::MidiControl2IdsValue(address0, address1, value) => {
                 write!(
                     f,
-                    "NoteOnOff({}, {}, {}, {}, {})",
-                    id0, id1, *value, note, intensity
+                    "MidiControl2IdsValue({}, {}, {})",
+                    address0, address1, value
                 )
             }
-            Self::MidiControlIdValue(id, value) => {
-                write!(f, "MidiControlIdValue({}, {})", id, value)
-            }
-            Self::MidiControl2IdsValue(id0, id1, value) => {
-                write!(f, "MidiControl2IdsValue({}, {}, {})", id0, id1, value)
-            }
         }
     }
 }
 
 impl std::fmt::Debug for MidiMsgAdvanced {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::MidiEmpty => write!(f, "MidiEmpty"),
-            Self::MidiNoteOnOff(id0, id1, value, note, intensity) => {
-                write!(
-                    f,
-                    "NoteOnOff({}, {}, {}, {}, {})",
-                    id0, id1, *value, note, intensity
-                )
-            }
-            Self::MidiControlIdValue(id, value) => {
-                write!(f, "MidiControlIdValue({}, {})", id, value)
-            }
-            Self::MidiControl2IdsValue(id0, id1, value) => {
-                write!(f, "MidiControl2IdsValue({}, {}, {})", id0, id1, value)
-            }
-        }
+        std::fmt::Display::fmt(self, f)
     }
 }
 
 pub trait MidiMsgBase: Send + std::fmt::Display {
     fn type_of(&self) -> &str;
     fn get_data(&self) -> Vec<u8>;
-    fn get_id(&self) -> u16;
+    fn get_address(&self) -> Option<ControlAddress>;
     fn get_value(&self) -> u16;
     fn get_time(&self) -> u64;
+    /// name of the input port the message came from
+    fn get_port(&self) -> Option<&str> {
+        None
+    }
 }
 
 //a fixed size container to copy data out of real-time thread
@@ -218,10 +185,10 @@ impl MidiMsgBase for MidiMsgGeneric {
         "MidiMsgGeneric"
     }
     fn get_data(&self) -> Vec<u8> {
-        self.data.into_iter().collect()
+        self.data[..self.len].to_vec()
     }
-    fn get_id(&self) -> u16 {
-        u16::max_value()
+    fn get_address(&self) -> Option<ControlAddress> {
+        None
     }
     fn get_value(&self) -> u16 {
         0
@@ -258,10 +225,11 @@ impl std::fmt::Display for MidiMsgGeneric {
 
 impl From<jack::RawMidi<'_>> for MidiMsgGeneric {
     fn from(midi: jack::RawMidi<'_>) -> MidiMsgGeneric {
+        let len = std::cmp::min(MAX_MIDI, midi.bytes.len());
         let mut data: [u8; MAX_MIDI] = [0, 0, 0];
-        data[..MAX_MIDI].copy_from_slice(&midi.bytes[..MAX_MIDI]);
+        data[..len].copy_from_slice(&midi.bytes[..len]);
         MidiMsgGeneric {
-            len: MAX_MIDI,
+            len,
             data,
             time: midi.time as u64 + jack::get_time(),
         }
@@ -275,6 +243,12 @@ pub struct MidiMsgControlChange {
     pub time: u64,
 }
 
+impl MidiMsgControlChange {
+    pub fn address(&self) -> ControlAddress {
+        ControlAddress::control_change(self.channel, self.control)
+    }
+}
+
 impl MidiMsgBase for MidiMsgControlChange {
     fn type_of(&self) -> &str {
         "MidiMsgControlChange"
@@ -282,8 +256,8 @@ impl MidiMsgBase for MidiMsgControlChange {
     fn get_data(&self) -> Vec<u8> {
         vec![0xB0 + self.channel, self.control, self.value]
     }
-    fn get_id(&self) -> u16 {
-        0xB000 + ((self.channel as u16) << 8) + self.control as u16
+    fn get_address(&self) -> Option<ControlAddress> {
+        Some(self.address())
     }
     fn get_value(&self) -> u16 {
         self.value as u16
@@ -320,6 +294,12 @@ pub struct MidiMsgNoteOn {
     pub time: u64,
 }
 
+impl MidiMsgNoteOn {
+    pub fn address(&self) -> ControlAddress {
+        ControlAddress::note(self.channel, self.key)
+    }
+}
+
 impl MidiMsgBase for MidiMsgNoteOn {
     fn type_of(&self) -> &str {
         "MidiMsgNoteOn"
@@ -327,8 +307,8 @@ impl MidiMsgBase for MidiMsgNoteOn {
     fn get_data(&self) -> Vec<u8> {
         vec![0x90 + self.channel, self.key, self.velocity]
     }
-    fn get_id(&self) -> u16 {
-        0x9000 + ((self.channel as u16) << 8) + self.key as u16
+    fn get_address(&self) -> Option<ControlAddress> {
+        Some(self.address())
     }
     fn get_value(&self) -> u16 {
         self.velocity as u16
@@ -365,6 +345,12 @@ pub struct MidiMsgNoteOff {
     pub time: u64,
 }
 
+impl MidiMsgNoteOff {
+    pub fn address(&self) -> ControlAddress {
+        ControlAddress::note(self.channel, self.key)
+    }
+}
+
 impl MidiMsgBase for MidiMsgNoteOff {
     fn type_of(&self) -> &str {
         "MidiMsgNoteOff"
@@ -372,8 +358,8 @@ impl MidiMsgBase for MidiMsgNoteOff {
     fn get_data(&self) -> Vec<u8> {
         vec![0x80 + self.channel, self.key, self.velocity]
     }
-    fn get_id(&self) -> u16 {
-        0x8000 + ((self.channel as u16) << 8) + self.key as u16
+    fn get_address(&self) -> Option<ControlAddress> {
+        Some(self.address())
     }
     fn get_value(&self) -> u16 {
         self.velocity as u16
@@ -409,6 +395,12 @@ pub struct MidiMsgPitchBend {
     pub time: u64,
 }
 
+impl MidiMsgPitchBend {
+    pub fn address(&self) -> ControlAddress {
+        ControlAddress::pitch_bend(self.channel)
+    }
+}
+
 impl MidiMsgBase for MidiMsgPitchBend {
     fn type_of(&self) -> &str {
         "MidiMsgPitchBend"
@@ -417,8 +409,8 @@ impl MidiMsgBase for MidiMsgPitchBend {
         let (msb_value, lsb_value) = u14_to_msb_lsb(self.value);
         vec![0xE0 + self.channel, lsb_value, msb_value]
     }
-    fn get_id(&self) -> u16 {
-        0xE000 + ((self.channel as u16) << 8)
+    fn get_address(&self) -> Option<ControlAddress> {
+        Some(self.address())
     }
     fn get_value(&self) -> u16 {
         self.value
@@ -448,98 +440,157 @@ impl std::fmt::Display for MidiMsgPitchBend {
     }
 }
 
+pub struct MidiMsgSysEx {
+    pub data: Vec<u8>,
+    pub time: u64,
+}
+
+impl MidiMsgBase for MidiMsgSysEx {
+    fn type_of(&self) -> &str {
+        "MidiMsgSysEx"
+    }
+    fn get_data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+    fn get_address(&self) -> Option<ControlAddress> {
+        None
+    }
+    fn get_value(&self) -> u16 {
+        0
+    }
+    fn get_time(&self) -> u64 {
+        self.time
+    }
+}
+
+impl std::fmt::Debug for MidiMsgSysEx {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "MidiSysEx: time: {}, len: {}, data: {:02x?}",
+            self.time,
+            self.data.len(),
+            self.data,
+        )
+    }
+}
+
+impl std::fmt::Display for MidiMsgSysEx {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "MidiSysEx: time: {}, len: {}, data: {:02x?}",
+            self.time,
+            self.data.len(),
+            self.data,
+        )
+    }
+}
+
+/// Decode the raw bytes of a single midi message
+pub fn midi_msg_from_bytes(bytes: &[u8], time: u64) -> Box<dyn MidiMsgBase> {
+    if bytes.first() == Some(&0xf0) {
+        return Box::new(MidiMsgSysEx {
+            data: bytes.to_vec(),
+            time,
+        });
+    }
+    let len = std::cmp::min(MAX_MIDI, bytes.len());
+    let (status, channel) = from_status_byte(bytes.first().copied().unwrap_or(0));
+    if len == MAX_MIDI && status == 0x08 {
+        // NoteOff
+        Box::new(MidiMsgNoteOff {
+            channel,
+            key: mask7(bytes[1]),
+            velocity: mask7(bytes[2]),
+            time,
+        })
+    } else if len == MAX_MIDI && status == 0x09 {
+        // NoteOn
+        Box::new(MidiMsgNoteOn {
+            channel,
+            key: mask7(bytes[1]),
+            velocity: mask7(bytes[2]),
+            time,
+        })
+    } else if len == MAX_MIDI && status == 0x0b {
+        // MidiCC
+        Box::new(MidiMsgControlChange {
+            channel,
+            control: mask7(bytes[1]),
+            value: mask7(bytes[2]),
+            time,
+        })
+    } else if len == MAX_MIDI && status == 0x0e {
+        // MidiPitchBend
+        Box::new(MidiMsgPitchBend {
+            channel,
+            value: msb_lsb_to_u14(mask7(bytes[2]), mask7(bytes[1])),
+            time,
+        })
+    } else {
+        let mut data = [0; MAX_MIDI];
+        data[..len].copy_from_slice(&bytes[..len]);
+        Box::new(MidiMsgGeneric { len, data, time })
+    }
+}
+
 impl From<jack::RawMidi<'_>> for Box<dyn MidiMsgBase> {
     fn from(midi: jack::RawMidi<'_>) -> Box<dyn MidiMsgBase> {
-        let midi_time = midi.time as u64 + jack::get_time();
-        let len = std::cmp::min(MAX_MIDI, midi.bytes.len());
-        let (status, channel) = from_status_byte(midi.bytes[0]);
-        if status == 0x08 {
-            // NoteOff
-            Box::new(MidiMsgNoteOff {
-                channel,
-                key: mask7(midi.bytes[1]),
-                velocity: mask7(midi.bytes[2]),
-                time: midi_time,
-            })
-        } else if status == 0x09 {
-            // NoteOn
-            Box::new(MidiMsgNoteOn {
-                channel,
-                key: mask7(midi.bytes[1]),
-                velocity: mask7(midi.bytes[2]),
-                time: midi_time,
-            })
-        } else if status == 0x0b {
-            // MidiCC
-            Box::new(MidiMsgControlChange {
-                channel,
-                control: mask7(midi.bytes[1]),
-                value: mask7(midi.bytes[2]),
-                time: midi_time,
-            })
-        } else if status == 0x0e {
-            // MidiPitchBend
-            Box::new(MidiMsgPitchBend {
-                channel,
-                value: msb_lsb_to_u14(mask7(midi.bytes[2]), mask7(midi.bytes[1])),
-                time: midi_time,
-            })
-        } else {
-            let mut data = [0; MAX_MIDI];
-            data[..len].copy_from_slice(&midi.bytes[..len]);
-            Box::new(MidiMsgGeneric {
-                len,
-                data,
-                time: midi_time,
-            })
-        }
+        midi_msg_from_bytes(midi.bytes, midi.time as u64 + jack::get_time())
     }
 }
 
 impl From<MidiMsgGeneric> for Box<dyn MidiMsgBase> {
     fn from(midi: MidiMsgGeneric) -> Box<dyn MidiMsgBase> {
-        let midi_time = midi.time;
-        let len = midi.len;
-        let (status, channel) = from_status_byte(midi.data[0]);
-        if status == 0x08 {
-            // NoteOff
-            Box::new(MidiMsgNoteOff {
-                channel,
-                key: mask7(midi.data[1]),
-                velocity: mask7(midi.data[2]),
-                time: midi_time,
-            })
-        } else if status == 0x09 {
-            // NoteOn
-            Box::new(MidiMsgNoteOn {
-                channel,
-                key: mask7(midi.data[1]),
-                velocity: mask7(midi.data[2]),
-                time: midi_time,
-            })
-        } else if status == 0x0b {
-            // MidiCC
-            Box::new(MidiMsgControlChange {
-                channel,
-                control: mask7(midi.data[1]),
-                value: mask7(midi.data[2]),
-                time: midi_time,
-            })
-        } else if status == 0x0e {
-            // MidiPitchBend
-            Box::new(MidiMsgPitchBend {
-                channel,
-                value: msb_lsb_to_u14(mask7(midi.data[2]), mask7(midi.data[1])),
-                time: midi_time,
-            })
-        } else {
-            let mut data = [0; MAX_MIDI];
-            data[..len].copy_from_slice(&midi.data[..len]);
-            Box::new(MidiMsgGeneric {
-                len,
-                data,
-                time: midi_time,
-            })
-        }
+        midi_msg_from_bytes(&midi.data[..midi.len], midi.time)
+    }
+}
+
+/// A message tagged with the input port it came from.
+/// The port becomes part of the control address, so identical controllers
+/// on different ports are different controls.
+pub struct MidiMsgWithPort {
+    pub port: Arc<str>,
+    pub midi_msg: Box<dyn MidiMsgBase>,
+}
+
+impl MidiMsgBase for MidiMsgWithPort {
+    fn type_of(&self) -> &str {
+        self.midi_msg.type_of()
+    }
+    fn get_data(&self) -> Vec<u8> {
+        self.midi_msg.get_data()
+    }
+    fn get_address(&self) -> Option<ControlAddress> {
+        self.midi_msg
+            .get_address()
+            .map(|address| address.with_port(Some(self.port.to_string())))
+    }
+    fn get_value(&self) -> u16 {
+        self.midi_msg.get_value()
+    }
+    fn get_time(&self) -> u64 {
+        self.midi_msg.get_time()
+    }
+    fn get_port(&self) -> Option<&str> {
+        Some(&self.port)
+    }
+}
+
+impl std::fmt::Display for MidiMsgWithPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.port, self.midi_msg)
+    }
+}
+
+/// Tag a message with its input port, messages without port are returned unchanged
+pub fn midi_msg_with_port(
+    midi_msg: Box<dyn MidiMsgBase>,
+    port: Option<Arc<str>>,
+) -> Box<dyn MidiMsgBase> {
+    match port {
+        Some(port) => Box::new(MidiMsgWithPort { port, midi_msg }),
+        None => midi_msg,
     }
 }
@@ -17,27 +17,419 @@
 
 extern crate jack;
 
-use crate::jackmidi::MidiMsgBase;
+use crate::jackmidi::{midi_msg_from_bytes, midi_msg_with_port, MidiMsgBase};
+use jack::PortSpec;
+use regex::Regex;
 
-use std::{process::exit, thread, time::Duration};
+use std::{
+    process::exit,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// name of the single input port if none are configured
+pub const DEFAULT_INPUT_PORT: &str = "midi_in";
+
+/// Connects the own port `input_port`, or all own ports if it is `None`,
+/// to every jack midi port of other clients whose full name matches `pattern`,
+/// own inputs to outputs and own outputs to inputs
+#[derive(Clone, Debug)]
+pub struct AutoConnect {
+    pub input_port: Option<String>,
+    pub pattern: Regex,
+}
+
+impl FromStr for AutoConnect {
+    type Err = String;
+
+    /// Parse `regex` or `input_port=regex`, e.g. `deck_a=a2j:.*nanoKONTROL.*`.
+    /// The regex has to match the whole port name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (input_port, pattern) = match s.split_once('=') {
+            Some((input_port, pattern))
+                if !input_port.is_empty()
+                    && input_port
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+            {
+                (Some(input_port.to_string()), pattern)
+            }
+            _ => (None, s),
+        };
+        let pattern = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|err| format!("invalid port pattern {}", err))?;
+        Ok(AutoConnect {
+            input_port,
+            pattern,
+        })
+    }
+}
+
+pub enum JackCommand {
+    /// connect source port to destination port, both full jack names
+    Connect(String, String),
+    Disconnect(String, String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JackPortInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// full names of the connected ports
+    pub connections: Vec<String>,
+}
+
+/// The jack midi ports, the own ports are the ones of this program
+#[derive(Clone, Debug, Default)]
+pub struct JackGraph {
+    pub own_inputs: Vec<JackPortInfo>,
+    pub own_outputs: Vec<JackPortInfo>,
+    /// midi outputs of other clients
+    pub sources: Vec<JackPortInfo>,
+    /// midi inputs of other clients
+    pub destinations: Vec<JackPortInfo>,
+}
+
+impl JackGraph {
+    fn read(client: &jack::Client) -> Self {
+        let own_prefix = format!("{}:", client.name());
+        let midi_type = Some(jack::MidiIn.jack_port_type());
+        let output_names = client.ports(None, midi_type, jack::PortFlags::IS_OUTPUT);
+        let input_names = client.ports(None, midi_type, jack::PortFlags::IS_INPUT);
+        let mut outputs = Vec::new();
+        for name in output_names.iter() {
+            let Some(port) = client.port_by_name(name) else {
+                continue;
+            };
+            let connections = input_names
+                .iter()
+                .filter(|input_name| port.is_connected_to(input_name).unwrap_or(false))
+                .cloned()
+                .collect();
+            outputs.push(JackPortInfo {
+                name: name.clone(),
+                aliases: port.aliases().unwrap_or_default(),
+                connections,
+            });
+        }
+        let mut inputs = Vec::new();
+        for name in input_names.iter() {
+            let Some(port) = client.port_by_name(name) else {
+                continue;
+            };
+            let connections = outputs
+                .iter()
+                .filter(|output: &&JackPortInfo| output.connections.contains(name))
+                .map(|output| output.name.clone())
+                .collect();
+            inputs.push(JackPortInfo {
+                name: name.clone(),
+                aliases: port.aliases().unwrap_or_default(),
+                connections,
+            });
+        }
+        let (own_outputs, sources) = outputs
+            .into_iter()
+            .partition(|port_info| port_info.name.starts_with(&own_prefix));
+        let (own_inputs, destinations) = inputs
+            .into_iter()
+            .partition(|port_info| port_info.name.starts_with(&own_prefix));
+        JackGraph {
+            own_inputs,
+            own_outputs,
+            sources,
+            destinations,
+        }
+    }
+}
+
+/// Handle of a GUI to list and connect the jack ports of the jack thread
+#[derive(Clone)]
+pub struct JackConnectionManager {
+    tx_command: crossbeam_channel::Sender<JackCommand>,
+    graph: Arc<Mutex<JackGraph>>,
+}
+
+/// The jack thread side of a `JackConnectionManager`
+pub struct JackGraphUpdater {
+    rx_command: crossbeam_channel::Receiver<JackCommand>,
+    graph: Arc<Mutex<JackGraph>>,
+}
+
+impl JackConnectionManager {
+    /// The updater has to be given to `start_jack_thread_with_ports`
+    pub fn new() -> (Self, JackGraphUpdater) {
+        let (tx_command, rx_command) = crossbeam_channel::unbounded();
+        let graph = Arc::new(Mutex::new(JackGraph::default()));
+        (
+            JackConnectionManager {
+                tx_command,
+                graph: graph.clone(),
+            },
+            JackGraphUpdater { rx_command, graph },
+        )
+    }
+
+    pub fn connect(&self, source: &str, destination: &str) {
+        let _ = self.tx_command.send(JackCommand::Connect(
+            source.to_string(),
+            destination.to_string(),
+        ));
+    }
+
+    pub fn disconnect(&self, source: &str, destination: &str) {
+        let _ = self.tx_command.send(JackCommand::Disconnect(
+            source.to_string(),
+            destination.to_string(),
+        ));
+    }
+
+    /// Copy of the last state of the jack graph
+    pub fn graph(&self) -> JackGraph {
+        self.graph.lock().unwrap().clone()
+    }
+}
+
+impl JackGraphUpdater {
+    fn update(&self, client: &jack::Client) {
+        *self.graph.lock().unwrap() = JackGraph::read(client);
+    }
+
+    fn execute(&self, client: &jack::Client, command: JackCommand) {
+        let result = match command {
+            JackCommand::Connect(ref source, ref destination) => {
+                client.connect_ports_by_name(source, destination)
+            }
+            JackCommand::Disconnect(ref source, ref destination) => {
+                client.disconnect_ports_by_name(source, destination)
+            }
+        };
+        if let Err(err) = result {
+            println!("jack connection error: {:?}", err);
+        }
+    }
+}
+
+/// Wakes up the jack thread when ports appear or connections change,
+/// jack doesn't allow to connect ports inside of its notification callbacks
+struct GraphNotifier {
+    tx_port_registered: crossbeam_channel::Sender<()>,
+    tx_graph_changed: crossbeam_channel::Sender<()>,
+}
+
+impl jack::NotificationHandler for GraphNotifier {
+    fn port_registration(&mut self, _: &jack::Client, _port_id: jack::PortId, is_registered: bool) {
+        if is_registered {
+            let _ = self.tx_port_registered.try_send(());
+        }
+        let _ = self.tx_graph_changed.try_send(());
+    }
+
+    fn port_rename(
+        &mut self,
+        _: &jack::Client,
+        _port_id: jack::PortId,
+        _old_name: &str,
+        _new_name: &str,
+    ) -> jack::Control {
+        let _ = self.tx_graph_changed.try_send(());
+        jack::Control::Continue
+    }
+
+    fn ports_connected(
+        &mut self,
+        _: &jack::Client,
+        _port_id_a: jack::PortId,
+        _port_id_b: jack::PortId,
+        _are_connected: bool,
+    ) {
+        let _ = self.tx_graph_changed.try_send(());
+    }
+}
+
+/// `own_port_names` are pairs of port name and full jack name of the own ports.
+/// Own inputs are connected to the matching sources, own outputs to the matching destinations.
+fn auto_connect_ports(
+    client: &jack::Client,
+    auto_connects: &[AutoConnect],
+    own_port_names: &[(String, String)],
+    own_ports_are_inputs: bool,
+) {
+    let own_prefix = format!("{}:", client.name());
+    let other_ports = client.ports(
+        None,
+        Some(jack::MidiIn.jack_port_type()),
+        if own_ports_are_inputs {
+            jack::PortFlags::IS_OUTPUT
+        } else {
+            jack::PortFlags::IS_INPUT
+        },
+    );
+    for other_port in other_ports
+        .iter()
+        .filter(|other_port| !other_port.starts_with(&own_prefix))
+    {
+        for auto_connect in auto_connects
+            .iter()
+            .filter(|auto_connect| auto_connect.pattern.is_match(other_port))
+        {
+            for (own_port, own_port_name) in own_port_names {
+                if auto_connect
+                    .input_port
+                    .as_ref()
+                    .is_some_and(|auto_connect_port| auto_connect_port != own_port)
+                {
+                    continue;
+                }
+                let (source, destination) = if own_ports_are_inputs {
+                    (other_port, own_port_name)
+                } else {
+                    (own_port_name, other_port)
+                };
+                match client.connect_ports_by_name(source, destination) {
+                    Ok(()) => println!("connected {} to {}", source, destination),
+                    Err(jack::Error::PortAlreadyConnected(_, _)) => {}
+                    Err(err) => {
+                        println!("could not connect {} to {}: {:?}", source, destination, err)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks that the ports named in `auto_connects` are known
+fn check_auto_connect_ports(auto_connects: &[AutoConnect], own_ports: &[String]) {
+    for auto_connect in auto_connects.iter() {
+        if let Some(ref port) = auto_connect.input_port {
+            if !own_ports.contains(port) {
+                println!("auto connect: unknown port {}", port);
+            }
+        }
+    }
+}
+
+/// Activates the client and runs until `rx_close` tells to stop.
+/// The own ports are auto connected at start and whenever a new jack port appears.
+fn run_jack_client<P>(
+    client: jack::Client,
+    process: P,
+    rx_close: crossbeam_channel::Receiver<bool>,
+    auto_connects: Vec<AutoConnect>,
+    own_port_names: Vec<(String, String)>,
+    own_ports_are_inputs: bool,
+    jack_graph_updater: Option<JackGraphUpdater>,
+) where
+    P: 'static + Send + jack::ProcessHandler,
+{
+    // bounded, a burst of changes gives a single reconnect or update
+    let (tx_port_registered, rx_port_registered) = crossbeam_channel::bounded(1);
+    let (tx_graph_changed, rx_graph_changed) = crossbeam_channel::bounded(1);
+    let notifier = GraphNotifier {
+        tx_port_registered,
+        tx_graph_changed,
+    };
+    let active_client = client.activate_async(notifier, process).unwrap();
+    let auto_connect = || {
+        if !auto_connects.is_empty() {
+            auto_connect_ports(
+                active_client.as_client(),
+                &auto_connects,
+                &own_port_names,
+                own_ports_are_inputs,
+            );
+        }
+    };
+    auto_connect();
+    let rx_command = match jack_graph_updater {
+        Some(ref jack_graph_updater) => {
+            jack_graph_updater.update(active_client.as_client());
+            jack_graph_updater.rx_command.clone()
+        }
+        None => crossbeam_channel::never(),
+    };
+
+    loop {
+        crossbeam_channel::select! {
+            recv(rx_close) -> running => {
+                if !running.unwrap_or(false) {
+                    break;
+                }
+            }
+            recv(rx_port_registered) -> _ => auto_connect(),
+            recv(rx_graph_changed) -> _ => {
+                if let Some(ref jack_graph_updater) = jack_graph_updater {
+                    jack_graph_updater.update(active_client.as_client());
+                }
+            }
+            recv(rx_command) -> command => {
+                if let (Some(ref jack_graph_updater), Ok(command)) = (&jack_graph_updater, command) {
+                    jack_graph_updater.execute(active_client.as_client(), command);
+                    jack_graph_updater.update(active_client.as_client());
+                }
+            }
+        }
+    }
+    let _ = active_client.deactivate();
+}
 
 pub fn start_jack_thread(
     rx_close: crossbeam_channel::Receiver<bool>,
     midi_sender: std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
     device_name: String,
 ) -> std::thread::JoinHandle<()> {
+    start_jack_thread_with_ports(
+        rx_close,
+        midi_sender,
+        device_name,
+        vec![DEFAULT_INPUT_PORT.to_string()],
+        Vec::new(),
+        None,
+    )
+}
+
+/// Registers one jack input port `<device_name>_<input_port>` per entry of `input_ports`,
+/// `DEFAULT_INPUT_PORT` if it is empty.
+/// Messages are tagged with the name from `input_ports` they came from.
+/// The ports are connected by `auto_connects` at start and whenever a new jack port appears.
+/// With a `jack_graph_updater` the jack thread executes the commands of its
+/// `JackConnectionManager` and keeps its graph up to date.
+pub fn start_jack_thread_with_ports(
+    rx_close: crossbeam_channel::Receiver<bool>,
+    midi_sender: std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
+    device_name: String,
+    input_ports: Vec<String>,
+    auto_connects: Vec<AutoConnect>,
+    jack_graph_updater: Option<JackGraphUpdater>,
+) -> std::thread::JoinHandle<()> {
+    let input_ports = if input_ports.is_empty() {
+        vec![DEFAULT_INPUT_PORT.to_string()]
+    } else {
+        input_ports
+    };
+    check_auto_connect_ports(&auto_connects, &input_ports);
     std::thread::spawn(move || {
-        let mut run: bool = true;
         let (client, _status) =
             jack::Client::new(&device_name, jack::ClientOptions::NO_START_SERVER)
                 .expect("No Jack server running\n");
 
         let sample_rate = client.sample_rate();
         // register ports
-        let midi_in_name: String = device_name.to_string() + "_midi_in";
-        let midi_in = client
-            .register_port(&midi_in_name, jack::MidiIn::default())
-            .unwrap();
+        let midi_ins: Vec<(Arc<str>, jack::Port<jack::MidiIn>)> = input_ports
+            .iter()
+            .map(|input_port| {
+                let midi_in_name = format!("{}_{}", device_name, input_port);
+                let midi_in = client.register_port(&midi_in_name, jack::MidiIn).unwrap();
+                (Arc::from(input_port.as_str()), midi_in)
+            })
+            .collect();
+        let midi_in_names: Vec<(String, String)> = midi_ins
+            .iter()
+            .map(|(input_port, midi_in)| (input_port.to_string(), midi_in.name().unwrap()))
+            .collect();
 
         let mut frame_size = client.buffer_size() as usize;
 
@@ -51,24 +443,83 @@ pub fn start_jack_thread(
         } else {
             exit(-1);
         }
+        let process_callback =
+            move |client: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+                let cycle_start = ps.last_frame_time();
+                // port by port, so the MSB and LSB of 14 bit controls stay next to each other
+                for (input_port, midi_in) in midi_ins.iter() {
+                    for e in midi_in.iter(ps) {
+                        // microseconds of the jack clock the message was received at
+                        let time = client.frames_to_time(cycle_start.wrapping_add(e.time));
+                        let c = midi_msg_with_port(
+                            midi_msg_from_bytes(e.bytes, time),
+                            Some(input_port.clone()),
+                        );
+                        let _ = midi_sender.try_send(c);
+                    }
+                }
+                jack::Control::Continue
+            };
+        let process = jack::ClosureProcessHandler::new(process_callback);
+        run_jack_client(
+            client,
+            process,
+            rx_close,
+            auto_connects,
+            midi_in_names,
+            true,
+            jack_graph_updater,
+        );
+    })
+}
+
+/// Registers the jack output port `<device_name>_<output_port>` and writes the raw midi messages
+/// received by `rx_midi_out` to it.
+/// Messages which don't fit into the port buffer are counted in `failed_writes`.
+/// `auto_connects` connect the port to matching jack midi inputs of other clients.
+/// With a `jack_graph_updater` the jack thread executes the commands of its
+/// `JackConnectionManager` and keeps its graph up to date.
+pub fn start_jack_output_thread(
+    rx_close: crossbeam_channel::Receiver<bool>,
+    rx_midi_out: crossbeam_channel::Receiver<Vec<u8>>,
+    device_name: String,
+    output_port: String,
+    auto_connects: Vec<AutoConnect>,
+    jack_graph_updater: Option<JackGraphUpdater>,
+    failed_writes: Arc<AtomicUsize>,
+) -> std::thread::JoinHandle<()> {
+    check_auto_connect_ports(&auto_connects, std::slice::from_ref(&output_port));
+    std::thread::spawn(move || {
+        let (client, _status) =
+            jack::Client::new(&device_name, jack::ClientOptions::NO_START_SERVER)
+                .expect("No Jack server running\n");
+        let midi_out_name = format!("{}_{}", device_name, output_port);
+        let mut midi_out = client.register_port(&midi_out_name, jack::MidiOut).unwrap();
+        let midi_out_names = vec![(output_port, midi_out.name().unwrap())];
         let process_callback = move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
-            let show_p = midi_in.iter(ps);
-            for e in show_p {
-                let c: Box<dyn MidiMsgBase> = e.into();
-                let _ = midi_sender.try_send(c);
+            let mut writer = midi_out.writer(ps);
+            // all at the start of the cycle, in order, so 14 bit pairs stay together
+            while let Ok(bytes) = rx_midi_out.try_recv() {
+                let raw_midi = jack::RawMidi {
+                    time: 0,
+                    bytes: &bytes,
+                };
+                // no printing in the real-time thread
+                if writer.write(&raw_midi).is_err() {
+                    failed_writes.fetch_add(1, Ordering::Relaxed);
+                }
             }
             jack::Control::Continue
         };
         let process = jack::ClosureProcessHandler::new(process_callback);
-        let active_client = client.activate_async((), process).unwrap();
-
-        while run {
-            thread::sleep(Duration::from_millis(100));
-            match rx_close.recv() {
-                Ok(running) => run = running,
-                Err(_) => run = false,
-            }
-        }
-        let _ = active_client.deactivate();
+        run_jack_client(
+            client,
+            process,
+            rx_close,
+            auto_connects,
+            midi_out_names,
+            false,
+            jack_graph_updater,
+        );
     })
 }
@@ -15,9 +15,26 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+pub mod binding_conflicts;
+pub mod control_address;
+pub mod control_classifier;
+pub mod controll_element;
+pub mod controller_profile;
+pub mod drag_and_drop;
+pub mod file_format;
+pub mod jack_connection_panel;
 pub mod jackmidi;
 pub mod jackprocess;
+pub mod mapping_validation;
+pub mod midi_capture;
+pub mod midi_capture_export;
+pub mod midi_decode;
 pub mod midi_egui_elements;
+pub mod midi_filter;
 pub mod midi_function;
-pub mod controll_element;
+pub mod midi_history;
+pub mod midi_learn;
+pub mod midi_mapping;
+pub mod midi_player;
+pub mod smf;
 pub mod util;
@@ -0,0 +1,543 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::binding_conflicts::{find_binding_conflicts, BindingConflict};
+use crate::control_address::ControlAddress;
+use crate::controller_profile::ControllerProfile;
+use crate::file_format::parse_file;
+use crate::midi_function::{MidiFunctionFile, MidiFunctionWithElementsFile};
+use crate::midi_mapping::{
+    is_versioned_mapping, migrate_v1_entry, MidiBinding, MidiMappingFile, MIDI_MAPPING_VERSION,
+};
+use jsonschema::JSONSchema;
+use schemars::schema_for;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A single problem found in a file, `path` is a json pointer to the value
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ValidationIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum MappingFileKind {
+    Functions,
+    FunctionsWithElements,
+    Mapping,
+    LegacyMapping,
+}
+
+impl MappingFileKind {
+    /// Old mapping files are a map of function names to lists,
+    /// so functions named "version" or "midi_functions" are told apart by their values
+    pub fn detect(value: &Value) -> Self {
+        let Value::Object(map) = value else {
+            return MappingFileKind::LegacyMapping;
+        };
+        if is_versioned_mapping(map) {
+            return MappingFileKind::Mapping;
+        }
+        match map.get("midi_functions") {
+            Some(Value::Array(midi_functions))
+                if midi_functions
+                    .iter()
+                    .all(|midi_function| midi_function.get("name").is_some()) =>
+            {
+                MappingFileKind::Functions
+            }
+            Some(Value::Object(_)) => MappingFileKind::FunctionsWithElements,
+            _ => MappingFileKind::LegacyMapping,
+        }
+    }
+
+    /// The json schema of the file kind, old mapping files have none
+    pub fn schema(&self) -> Option<Value> {
+        match self {
+            MappingFileKind::Functions => Some(midi_function_file_schema()),
+            MappingFileKind::FunctionsWithElements => {
+                Some(midi_function_with_elements_file_schema())
+            }
+            MappingFileKind::Mapping => Some(midi_mapping_file_schema()),
+            MappingFileKind::LegacyMapping => None,
+        }
+    }
+}
+
+pub fn midi_function_file_schema() -> Value {
+    serde_json::to_value(schema_for!(MidiFunctionFile)).unwrap()
+}
+
+pub fn midi_function_with_elements_file_schema() -> Value {
+    serde_json::to_value(schema_for!(MidiFunctionWithElementsFile)).unwrap()
+}
+
+pub fn midi_mapping_file_schema() -> Value {
+    serde_json::to_value(schema_for!(MidiMappingFile)).unwrap()
+}
+
+pub fn controller_profile_schema() -> Value {
+    serde_json::to_value(schema_for!(ControllerProfile)).unwrap()
+}
+
+fn pointer(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+struct Validator<'a> {
+    known_functions: Option<HashSet<String>>,
+    issues: &'a mut Vec<ValidationIssue>,
+}
+
+impl Validator<'_> {
+    fn issue(&mut self, path: String, message: String) {
+        self.issues.push(ValidationIssue { path, message });
+    }
+
+    fn check_schema(&mut self, schema: &Value, value: &Value) {
+        let compiled = match JSONSchema::compile(schema) {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                self.issue(String::new(), format!("invalid schema {}", err));
+                return;
+            }
+        };
+        let schema_issues: Vec<ValidationIssue> = match compiled.validate(value) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|error| ValidationIssue {
+                    path: error.instance_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect(),
+        };
+        self.issues.extend(schema_issues);
+    }
+
+    fn check_function_name(&mut self, root: &[&str], name: &str) {
+        if let Some(ref known_functions) = self.known_functions {
+            if !known_functions.contains(name) {
+                self.issue(
+                    pointer(&[root, &[name]].concat()),
+                    format!("unknown function {}", name),
+                );
+            }
+        }
+    }
+
+    fn check_functions(&mut self, value: &Value) {
+        let Ok(midi_functions) = serde_json::from_value::<MidiFunctionFile>(value.clone()) else {
+            return;
+        };
+        let mut names = HashSet::new();
+        for (idx, midi_function) in midi_functions.midi_functions.iter().enumerate() {
+            let name = midi_function.get_name();
+            if !names.insert(name.clone()) {
+                self.issue(
+                    pointer(&["midi_functions", &idx.to_string(), "name"]),
+                    format!("duplicate function {}", name),
+                );
+            }
+        }
+    }
+
+    fn check_functions_with_elements(&mut self, value: &Value) {
+        let Ok(elements) = serde_json::from_value::<MidiFunctionWithElementsFile>(value.clone())
+        else {
+            return;
+        };
+        let mut names: Vec<&String> = elements.midi_functions.keys().collect();
+        names.sort();
+        for name in names {
+            self.check_function_name(&["midi_functions"], name);
+            let mut addresses = HashSet::new();
+            for (idx, address) in elements.midi_functions[name].iter().enumerate() {
+                if !addresses.insert(address) {
+                    self.issue(
+                        pointer(&["midi_functions", name, &idx.to_string()]),
+                        format!("duplicate binding {}", address),
+                    );
+                }
+            }
+        }
+    }
+
+    /// `root` is the path to the function names, old mapping files have them at the top.
+    /// Bindings which can't be read are `None`, the schema check reports them.
+    fn check_bindings(&mut self, root: &[&str], name: &str, bindings: &[Option<MidiBinding>]) {
+        self.check_function_name(root, name);
+        let mut seen_bindings = HashSet::new();
+        for (idx, binding) in bindings.iter().enumerate() {
+            let Some(binding) = binding else {
+                continue;
+            };
+            let path = pointer(&[root, &[name, &idx.to_string()]].concat());
+            if !seen_bindings.insert(binding.without_mode()) {
+                self.issue(path.clone(), format!("duplicate binding {}", binding));
+            }
+            // the schema only tells that no binding type matched
+            for (field, range_issue) in binding.control.range_issues() {
+                self.issue(format!("{}/{}", path, field), range_issue);
+            }
+        }
+    }
+
+    fn check_mapping(&mut self, value: &Value) {
+        let Some(midi_functions) = value.get("midi_functions").and_then(Value::as_object) else {
+            return;
+        };
+        for (name, entries) in midi_functions {
+            let Some(entries) = entries.as_array() else {
+                continue;
+            };
+            let bindings: Vec<Option<MidiBinding>> = entries
+                .iter()
+                .map(|entry| serde_json::from_value(entry.clone()).ok())
+                .collect();
+            self.check_bindings(&["midi_functions"], name, &bindings);
+        }
+    }
+
+    fn check_legacy_mapping(&mut self, value: &Value) {
+        let Value::Object(map) = value else {
+            self.issue(
+                String::new(),
+                "mapping file has to be an object".to_string(),
+            );
+            return;
+        };
+        for (name, entries) in map {
+            let Value::Array(entries) = entries else {
+                self.issue(
+                    pointer(&[name]),
+                    format!("bindings of function {} are not a list", name),
+                );
+                continue;
+            };
+            for (idx, entry) in entries.iter().enumerate() {
+                self.check_legacy_entry(pointer(&[name, &idx.to_string()]), entry);
+            }
+            self.check_legacy_note_ids(map, name, entries);
+            let bindings: Vec<Option<MidiBinding>> = entries.iter().map(migrate_v1_entry).collect();
+            self.check_bindings(&[], name, &bindings);
+        }
+    }
+
+    /// A packed note off id is read as the whole note,
+    /// bound next to its note on id the note ends up bound twice
+    fn check_legacy_note_ids(
+        &mut self,
+        map: &serde_json::Map<String, Value>,
+        name: &str,
+        entries: &[Value],
+    ) {
+        let note_on_ids: HashSet<u64> = map
+            .values()
+            .filter_map(Value::as_array)
+            .flatten()
+            .filter_map(Value::as_u64)
+            .filter(|id| id >> 12 == 0x09)
+            .collect();
+        for (idx, entry) in entries.iter().enumerate() {
+            let Some(id_off) = entry.as_u64().filter(|id| id >> 12 == 0x08) else {
+                continue;
+            };
+            let id_on = id_off + 0x1000;
+            if note_on_ids.contains(&id_on) {
+                self.issue(
+                    pointer(&[name, &idx.to_string()]),
+                    format!(
+                        "note off id {} is bound separately from note on id {}, both are read as the same note",
+                        id_off, id_on
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_legacy_entry(&mut self, path: String, entry: &Value) {
+        let legacy_id =
+            |value: Option<&Value>| -> Option<u16> { u16::try_from(value?.as_u64()?).ok() };
+        if entry.is_number() {
+            if legacy_id(Some(entry))
+                .and_then(ControlAddress::from_legacy_id)
+                .is_none()
+            {
+                self.issue(path, format!("invalid midi id {}", entry));
+            }
+            return;
+        }
+        let Some((variant, fields)) = entry.as_object().and_then(|map| map.iter().next()) else {
+            self.issue(path, format!("invalid binding {}", entry));
+            return;
+        };
+        let ids = [legacy_id(fields.get(0)), legacy_id(fields.get(1))];
+        match (variant.as_str(), ids) {
+            ("MidiNoteOnOff", [Some(id_on), Some(id_off)]) => {
+                if id_on >> 12 != 0x09 || id_on.checked_sub(0x1000) != Some(id_off) {
+                    self.issue(
+                        path,
+                        format!(
+                            "note on id {} and note off id {} don't match",
+                            id_on, id_off
+                        ),
+                    );
+                }
+            }
+            ("MidiControlIdValue", [Some(id), _])
+            | ("MidiControl2IdsValue", [Some(id), Some(_)]) => {
+                if ControlAddress::from_legacy_id(id).is_none() {
+                    self.issue(path, format!("invalid midi id {}", id));
+                }
+            }
+            _ => self.issue(path, format!("invalid binding {}", entry)),
+        }
+    }
+}
+
+/// Check a parsed functions or mapping file against its schema and the semantic rules.
+/// If `midi_functions` is given, bound function names have to be part of it.
+pub fn validate_value(
+    value: &Value,
+    midi_functions: Option<&MidiFunctionFile>,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let mut validator = Validator {
+        known_functions: midi_functions.map(|midi_functions| {
+            midi_functions
+                .midi_functions
+                .iter()
+                .map(|midi_function| midi_function.get_name())
+                .collect()
+        }),
+        issues: &mut issues,
+    };
+    let kind = MappingFileKind::detect(value);
+    if kind == MappingFileKind::Mapping
+        && value.get("version").and_then(Value::as_u64) != Some(MIDI_MAPPING_VERSION as u64)
+    {
+        validator.issue(
+            "/version".to_string(),
+            format!("unsupported mapping file version {}", value["version"]),
+        );
+        return issues;
+    }
+    if let Some(schema) = kind.schema() {
+        validator.check_schema(&schema, value);
+    }
+    match kind {
+        MappingFileKind::Functions => validator.check_functions(value),
+        MappingFileKind::FunctionsWithElements => validator.check_functions_with_elements(value),
+        MappingFileKind::Mapping => validator.check_mapping(value),
+        MappingFileKind::LegacyMapping => validator.check_legacy_mapping(value),
+    }
+    // a value can have several problems, only exact repeats are dropped
+    issues.sort_by(|issue0, issue1| {
+        (&issue0.path, &issue0.message).cmp(&(&issue1.path, &issue1.message))
+    });
+    issues.dedup();
+    issues
+}
+
+/// Read a json, toml or yaml file and validate it, see `validate_value`
+pub fn validate_file(
+    file_path_str: &str,
+    midi_functions: Option<&MidiFunctionFile>,
+) -> Result<Vec<ValidationIssue>, String> {
+    let value: Value = parse_file(file_path_str)?;
+    Ok(validate_value(&value, midi_functions))
+}
+
+/// Overlapping bindings of a mapping file, see `find_binding_conflicts`.
+/// They are allowed, all overlapping functions are called. Other file kinds have no bindings.
+pub fn find_file_binding_conflicts(file_path_str: &str) -> Result<Vec<BindingConflict>, String> {
+    let value: Value = parse_file(file_path_str)?;
+    match MappingFileKind::detect(&value) {
+        MappingFileKind::Mapping | MappingFileKind::LegacyMapping => {
+            let midi_mapping = MidiMappingFile::from_json_value(value)?;
+            Ok(find_binding_conflicts(&midi_mapping.midi_functions))
+        }
+        MappingFileKind::Functions | MappingFileKind::FunctionsWithElements => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_function::MidiFunction;
+    use serde_json::json;
+
+    fn issue(path: &str, message: &str) -> ValidationIssue {
+        ValidationIssue {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn detect_file_kinds() {
+        let detect = |value| MappingFileKind::detect(&value);
+        assert_eq!(
+            detect(json!({"midi_functions": [{"name": "Volume"}]})),
+            MappingFileKind::Functions
+        );
+        assert_eq!(
+            detect(json!({"midi_functions": {"Volume": ["ch1:cc7"]}})),
+            MappingFileKind::FunctionsWithElements
+        );
+        assert_eq!(
+            detect(json!({"version": 2, "midi_functions": {}})),
+            MappingFileKind::Mapping
+        );
+        assert_eq!(
+            detect(json!({"Volume": [45319]})),
+            MappingFileKind::LegacyMapping
+        );
+        // functions of old mapping files named like the keys of the other files
+        assert_eq!(
+            detect(json!({"version": [45319], "midi_functions": [45320]})),
+            MappingFileKind::LegacyMapping
+        );
+    }
+
+    #[test]
+    fn unknown_function() {
+        let midi_functions = MidiFunctionFile {
+            midi_functions: vec![MidiFunction::new("Volume".to_string())],
+        };
+        let value = json!({"version": 2, "midi_functions": {
+            "Volume": [{"type": "cc", "channel": 1, "controller": 7}],
+            "Pan": [{"type": "cc", "channel": 1, "controller": 10}],
+        }});
+        assert_eq!(
+            validate_value(&value, Some(&midi_functions)),
+            vec![issue("/midi_functions/Pan", "unknown function Pan")]
+        );
+        assert_eq!(validate_value(&value, None), Vec::new());
+    }
+
+    #[test]
+    fn duplicate_binding() {
+        let value = json!({"version": 2, "midi_functions": {"Volume": [
+            {"type": "cc", "channel": 1, "controller": 7},
+            {"type": "cc", "channel": 1, "controller": 7, "mode": "absolute"},
+        ]}});
+        assert_eq!(
+            validate_value(&value, None),
+            vec![issue(
+                "/midi_functions/Volume/1",
+                "duplicate binding ch1:cc7 (absolute)"
+            )]
+        );
+    }
+
+    #[test]
+    fn legacy_note_on_off_mismatch_and_duplicate() {
+        let value = json!({"Volume": [
+            {"MidiNoteOnOff": [36906, 32811, false]},
+            {"MidiNoteOnOff": [36906, 32811, false]},
+        ]});
+        assert_eq!(
+            validate_value(&value, None),
+            vec![
+                issue(
+                    "/Volume/0",
+                    "note on id 36906 and note off id 32811 don't match"
+                ),
+                issue("/Volume/1", "duplicate binding ch1:note42"),
+                issue(
+                    "/Volume/1",
+                    "note on id 36906 and note off id 32811 don't match"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_split_note_ids() {
+        let value = json!({"Play": [36906], "Stop": [32810]});
+        assert_eq!(
+            validate_value(&value, None),
+            vec![issue(
+                "/Stop/0",
+                "note off id 32810 is bound separately from note on id 36906, both are read as the same note"
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_id() {
+        let value = json!({"Volume": [1234, {"MidiControlIdValue": [70000, 0]}, "cc7"]});
+        assert_eq!(
+            validate_value(&value, None),
+            vec![
+                issue("/Volume/0", "invalid midi id 1234"),
+                issue(
+                    "/Volume/1",
+                    "invalid binding {\"MidiControlIdValue\":[70000,0]}"
+                ),
+                issue("/Volume/2", "invalid binding \"cc7\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_channel() {
+        let value = json!({"version": 2, "midi_functions": {"Volume": [
+            {"type": "cc", "channel": 1, "controller": 7},
+            {"type": "cc", "channel": 17, "controller": 200},
+        ]}});
+        let issues = validate_value(&value, None);
+        assert!(issues.contains(&issue(
+            "/midi_functions/Volume/1/channel",
+            "channel 17 out of range 1..16"
+        )));
+        assert!(issues.contains(&issue(
+            "/midi_functions/Volume/1/controller",
+            "controller 200 out of range 0..127"
+        )));
+        // besides the schema error of the binding
+        assert!(issues
+            .iter()
+            .all(|issue| issue.path.starts_with("/midi_functions/Volume/1")));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let value = json!({"version": 3, "midi_functions": {}});
+        assert_eq!(
+            validate_value(&value, None),
+            vec![issue("/version", "unsupported mapping file version 3")]
+        );
+    }
+}
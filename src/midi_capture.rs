@@ -0,0 +1,139 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::file_format::read_file_to_string;
+use crate::jackmidi::{midi_msg_from_bytes, midi_msg_with_port, MidiMsgBase};
+use crate::smf::read_smf;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Raw bytes of a midi message with its timestamp in microseconds
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub struct MidiCaptureEvent {
+    pub time: u64,
+    pub data: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+}
+
+impl MidiCaptureEvent {
+    pub fn from_midi_msg(midi_msg: &dyn MidiMsgBase) -> Self {
+        MidiCaptureEvent {
+            time: midi_msg.get_time(),
+            data: midi_msg.get_data(),
+            port: midi_msg.get_port().map(str::to_string),
+        }
+    }
+
+    pub fn to_midi_msg(&self) -> Box<dyn MidiMsgBase> {
+        midi_msg_with_port(
+            midi_msg_from_bytes(&self.data, self.time),
+            self.port.as_deref().map(Arc::from),
+        )
+    }
+}
+
+/// Line of a json capture, marker rows of exported debugger lists are no midi messages
+#[derive(Deserialize)]
+struct JsonCaptureEntry {
+    #[serde(flatten)]
+    midi_capture_event: MidiCaptureEvent,
+    #[serde(default)]
+    marker: Option<String>,
+}
+
+impl JsonCaptureEntry {
+    fn into_midi_capture_event(self) -> Option<MidiCaptureEvent> {
+        match self.marker {
+            Some(_) => None,
+            None => Some(self.midi_capture_event),
+        }
+    }
+}
+
+/// Collects copies of midi messages between `start` and `stop`
+#[derive(Default)]
+pub struct MidiRecorder {
+    recording: bool,
+    events: Vec<MidiCaptureEvent>,
+}
+
+impl MidiRecorder {
+    /// Starts a new recording, events of the last one are dropped
+    pub fn start(&mut self) {
+        self.events.clear();
+        self.recording = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn record(&mut self, midi_msg: &dyn MidiMsgBase) {
+        if self.recording {
+            self.events.push(MidiCaptureEvent::from_midi_msg(midi_msg));
+        }
+    }
+
+    pub fn events(&self) -> &[MidiCaptureEvent] {
+        &self.events
+    }
+}
+
+/// Read a json capture, either a list of events or one event per line (json lines).
+/// Additional fields of the events are ignored, markers are skipped.
+pub fn parse_json_capture(file_path_str: &str) -> Result<Vec<MidiCaptureEvent>, String> {
+    let contents = read_file_to_string(file_path_str)?;
+    let json_capture_entries: Vec<JsonCaptureEntry> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(&contents)
+            .map_err(|err| format!("error in json deserialize {}", err))?
+    } else {
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line)
+                    .map_err(|err| format!("error in json deserialize line {}: {}", idx + 1, err))
+            })
+            .collect::<Result<_, String>>()?
+    };
+    Ok(json_capture_entries
+        .into_iter()
+        .filter_map(JsonCaptureEntry::into_midi_capture_event)
+        .collect())
+}
+
+/// Read a standard midi file (`.mid`, `.midi`, `.smf`) or a json capture sorted by time
+pub fn parse_capture_file(file_path_str: &str) -> Result<Vec<MidiCaptureEvent>, String> {
+    let extension = Path::new(file_path_str)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let mut midi_capture_events = match extension.as_deref() {
+        Some("mid") | Some("midi") | Some("smf") => read_smf(file_path_str)?,
+        _ => parse_json_capture(file_path_str)?,
+    };
+    // empty messages can't be sent
+    midi_capture_events.retain(|midi_capture_event| !midi_capture_event.data.is_empty());
+    midi_capture_events.sort_by_key(|midi_capture_event| midi_capture_event.time);
+    Ok(midi_capture_events)
+}
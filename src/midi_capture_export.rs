@@ -0,0 +1,293 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::midi_decode::hex_bytes;
+use crate::midi_filter::MsgCategory;
+use crate::midi_history::{HistoryEntry, MidiHistory};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const CSV_HEADER: [&str; 13] = [
+    "time",
+    "port",
+    "data",
+    "hex",
+    "kind",
+    "channel",
+    "number",
+    "value",
+    "value_14bit",
+    "description",
+    "delta",
+    "control_delta",
+    "marker",
+];
+
+/// One exported row of the debugger list.
+/// `time` and `data` are named like in `MidiCaptureEvent`, so json lines exports can be played back.
+/// Markers have no `data` and are skipped by the playback.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MidiCaptureRecord {
+    pub time: u64,
+    pub port: Option<String>,
+    pub data: Vec<u8>,
+    pub hex: String,
+    pub kind: String,
+    /// 1..16, only for channel messages
+    pub channel: Option<u8>,
+    /// note, controller or program number
+    pub number: Option<u8>,
+    pub value: Option<u16>,
+    pub value_14bit: Option<u16>,
+    pub description: String,
+    pub delta: Option<u64>,
+    pub control_delta: Option<u64>,
+    pub marker: Option<String>,
+}
+
+impl MidiCaptureRecord {
+    pub fn from_history_entry(entry: &HistoryEntry) -> Self {
+        let decoded_msg = match entry {
+            HistoryEntry::Msg(_, decoded_msg) => decoded_msg,
+            HistoryEntry::Marker { time, text } => {
+                return MidiCaptureRecord {
+                    time: *time,
+                    kind: "marker".to_string(),
+                    description: text.clone(),
+                    marker: Some(text.clone()),
+                    ..Default::default()
+                };
+            }
+        };
+        let data = decoded_msg.data.clone();
+        let status = data.first().copied().unwrap_or(0);
+        let data1 = data.get(1).copied();
+        let data2 = data.get(2).copied();
+        let (channel, number, value) = match status & 0xf0 {
+            0x80 | 0x90 | 0xa0 | 0xb0 => (
+                Some((status & 0x0f) + 1),
+                data1,
+                data2.map(|value| value as u16),
+            ),
+            0xc0 => (Some((status & 0x0f) + 1), data1, None),
+            0xd0 => (
+                Some((status & 0x0f) + 1),
+                None,
+                data1.map(|value| value as u16),
+            ),
+            0xe0 => (Some((status & 0x0f) + 1), None, decoded_msg.value_14bit),
+            _ => (None, None, None),
+        };
+        MidiCaptureRecord {
+            time: decoded_msg.time,
+            port: decoded_msg.port.clone(),
+            kind: MsgCategory::from_data(&data).name().to_string(),
+            channel,
+            number,
+            value,
+            value_14bit: decoded_msg.value_14bit,
+            description: decoded_msg.description.clone(),
+            delta: decoded_msg.delta,
+            control_delta: decoded_msg.control_delta,
+            marker: None,
+            hex: hex_bytes(&data),
+            data,
+        }
+    }
+
+    fn to_csv_record(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.time.to_string(),
+            optional(self.port.clone()),
+            self.data
+                .iter()
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+            self.hex.clone(),
+            self.kind.clone(),
+            optional(self.channel.map(|channel| channel.to_string())),
+            optional(self.number.map(|number| number.to_string())),
+            optional(self.value.map(|value| value.to_string())),
+            optional(self.value_14bit.map(|value| value.to_string())),
+            self.description.clone(),
+            optional(self.delta.map(|delta| delta.to_string())),
+            optional(self.control_delta.map(|delta| delta.to_string())),
+            optional(self.marker.clone()),
+        ]
+    }
+}
+
+pub fn midi_capture_records(history: &MidiHistory) -> Vec<MidiCaptureRecord> {
+    history
+        .iter()
+        .map(|(_, entry)| MidiCaptureRecord::from_history_entry(entry))
+        .collect()
+}
+
+/// CSV with a header line, times in microseconds and bytes as space separated decimals
+pub fn write_capture_csv(file_path_str: &str, records: &[MidiCaptureRecord]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(file_path_str)
+        .map_err(|err| format!("Could not create the file {}", err))?;
+    writer
+        .write_record(CSV_HEADER)
+        .map_err(|err| format!("Could not write csv {}", err))?;
+    for record in records {
+        writer
+            .write_record(record.to_csv_record())
+            .map_err(|err| format!("Could not write csv {}", err))?;
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("Could not write the file {}", err))
+}
+
+/// One json object per line
+pub fn write_capture_jsonl(
+    file_path_str: &str,
+    records: &[MidiCaptureRecord],
+) -> Result<(), String> {
+    let file =
+        File::create(file_path_str).map_err(|err| format!("Could not create the file {}", err))?;
+    let mut writer = BufWriter::new(file);
+    for record in records {
+        let line = serde_json::to_string(record)
+            .map_err(|err| format!("error in json serialize {}", err))?;
+        writeln!(writer, "{}", line).map_err(|err| format!("Could not write the file {}", err))?;
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("Could not write the file {}", err))
+}
+
+/// Write the records as csv for a `.csv` file and as json lines otherwise
+pub fn write_capture_records(
+    file_path_str: &str,
+    records: &[MidiCaptureRecord],
+) -> Result<(), String> {
+    let is_csv = Path::new(file_path_str)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        write_capture_csv(file_path_str, records)
+    } else {
+        write_capture_jsonl(file_path_str, records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jackmidi::{midi_msg_from_bytes, midi_msg_with_port};
+    use crate::midi_decode::MidiDecoder;
+
+    fn test_history() -> MidiHistory {
+        let mut midi_decoder = MidiDecoder::default();
+        let mut history = MidiHistory::default();
+        let midi_msgs = [
+            midi_msg_with_port(
+                midi_msg_from_bytes(&[0xb0, 7, 100], 1_000),
+                Some("synth, \"A\"".into()),
+            ),
+            midi_msg_from_bytes(&[0xe1, 0x00, 0x40], 1_500),
+        ];
+        for midi_msg in midi_msgs {
+            let decoded_msg = midi_decoder.decode(midi_msg.as_ref());
+            history.push(HistoryEntry::Msg(midi_msg, decoded_msg));
+        }
+        history.push_marker(2_000, "pressed \"PLAY\", here".to_string());
+        history
+    }
+
+    fn temp_file_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn records_of_messages_and_markers() {
+        let records = midi_capture_records(&test_history());
+        assert_eq!(records.len(), 3);
+
+        let control_change = &records[0];
+        assert_eq!(control_change.port.as_deref(), Some("synth, \"A\""));
+        assert_eq!(control_change.kind, "control_change");
+        assert_eq!(control_change.channel, Some(1));
+        assert_eq!(control_change.number, Some(7));
+        assert_eq!(control_change.value, Some(100));
+        assert_eq!(control_change.value_14bit, None);
+        assert_eq!(control_change.delta, None);
+
+        let pitch_bend = &records[1];
+        assert_eq!(pitch_bend.kind, "pitch_bend");
+        assert_eq!(pitch_bend.channel, Some(2));
+        assert_eq!(pitch_bend.number, None);
+        assert_eq!(pitch_bend.value, Some(8192));
+        assert_eq!(pitch_bend.delta, Some(500));
+
+        let marker = &records[2];
+        assert_eq!(marker.kind, "marker");
+        assert!(marker.data.is_empty());
+        assert_eq!(marker.marker.as_deref(), Some("pressed \"PLAY\", here"));
+    }
+
+    #[test]
+    fn csv_columns_and_escaping() {
+        let records = midi_capture_records(&test_history());
+        for record in records.iter() {
+            assert_eq!(record.to_csv_record().len(), CSV_HEADER.len());
+        }
+        let file_path = temp_file_path("capture.csv");
+        write_capture_records(&file_path, &records).unwrap();
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "time,port,data,hex,kind,channel,number,value,value_14bit,description,delta,control_delta,marker",
+                "1000,\"synth, \"\"A\"\"\",176 7 100,B0 07 64,control_change,1,7,100,,CC ch1 7 Channel Volume = 100,,,",
+                "1500,,225 0 64,E1 00 40,pitch_bend,2,,8192,8192,PitchBend ch2 8192,500,,",
+                "2000,,,,marker,,,,,\"pressed \"\"PLAY\"\", here\",,,\"pressed \"\"PLAY\"\", here\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_can_be_played_back() {
+        let records = midi_capture_records(&test_history());
+        let file_path = temp_file_path("capture.jsonl");
+        write_capture_records(&file_path, &records).unwrap();
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        let midi_capture_events = crate::midi_capture::parse_json_capture(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(contents.lines().count(), 3);
+        // the marker is skipped
+        assert_eq!(midi_capture_events.len(), 2);
+        assert_eq!(midi_capture_events[0].time, 1_000);
+        assert_eq!(midi_capture_events[0].data, vec![0xb0, 7, 100]);
+        assert_eq!(midi_capture_events[0].port.as_deref(), Some("synth, \"A\""));
+        assert_eq!(midi_capture_events[1].data, vec![0xe1, 0x00, 0x40]);
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::control_address::ControlAddress;
+use crate::jackmidi::MidiMsgBase;
+use std::collections::HashMap;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Name of a note number with middle C (60) as C4
+pub fn note_name(note: u8) -> String {
+    let octave = (note / 12) as i8 - 1;
+    format!("{}{}", NOTE_NAMES[(note % 12) as usize], octave)
+}
+
+/// General MIDI name of a controller, LSB controllers 32..63 get the name of their MSB
+pub fn gm_controller_name(controller: u8) -> Option<String> {
+    let name = match controller {
+        0 => "Bank Select",
+        1 => "Modulation Wheel",
+        2 => "Breath Controller",
+        4 => "Foot Controller",
+        5 => "Portamento Time",
+        6 => "Data Entry",
+        7 => "Channel Volume",
+        8 => "Balance",
+        10 => "Pan",
+        11 => "Expression",
+        12 => "Effect Control 1",
+        13 => "Effect Control 2",
+        16 => "General Purpose 1",
+        17 => "General Purpose 2",
+        18 => "General Purpose 3",
+        19 => "General Purpose 4",
+        32..=63 => return gm_controller_name(controller - 32).map(|name| name + " LSB"),
+        64 => "Sustain",
+        65 => "Portamento",
+        66 => "Sostenuto",
+        67 => "Soft Pedal",
+        68 => "Legato Footswitch",
+        69 => "Hold 2",
+        70 => "Sound Variation",
+        71 => "Resonance",
+        72 => "Release Time",
+        73 => "Attack Time",
+        74 => "Brightness",
+        75 => "Decay Time",
+        76 => "Vibrato Rate",
+        77 => "Vibrato Depth",
+        78 => "Vibrato Delay",
+        80 => "General Purpose 5",
+        81 => "General Purpose 6",
+        82 => "General Purpose 7",
+        83 => "General Purpose 8",
+        84 => "Portamento Control",
+        88 => "High Resolution Velocity Prefix",
+        91 => "Reverb Send",
+        92 => "Tremolo Depth",
+        93 => "Chorus Send",
+        94 => "Celeste Depth",
+        95 => "Phaser Depth",
+        96 => "Data Increment",
+        97 => "Data Decrement",
+        98 => "NRPN LSB",
+        99 => "NRPN MSB",
+        100 => "RPN LSB",
+        101 => "RPN MSB",
+        120 => "All Sound Off",
+        121 => "Reset All Controllers",
+        122 => "Local Control",
+        123 => "All Notes Off",
+        124 => "Omni Off",
+        125 => "Omni On",
+        126 => "Mono On",
+        127 => "Poly On",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+pub fn hex_bytes(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Microseconds as milliseconds with 3 decimals
+pub fn format_duration_us(duration: u64) -> String {
+    format!("{}.{:03} ms", duration / 1000, duration % 1000)
+}
+
+/// Human readable description of raw midi bytes, channels are shown as 1..16
+pub fn describe(data: &[u8]) -> String {
+    let Some(&status) = data.first() else {
+        return "Empty".to_string();
+    };
+    let channel = (status & 0x0f) + 1;
+    let data1 = data.get(1).copied().unwrap_or(0);
+    let data2 = data.get(2).copied().unwrap_or(0);
+    match status & 0xf0 {
+        0x80 => format!(
+            "NoteOff ch{} {} ({}) vel {}",
+            channel,
+            note_name(data1),
+            data1,
+            data2
+        ),
+        0x90 => format!(
+            "NoteOn ch{} {} ({}) vel {}",
+            channel,
+            note_name(data1),
+            data1,
+            data2
+        ),
+        0xa0 => format!(
+            "PolyAftertouch ch{} {} ({}) {}",
+            channel,
+            note_name(data1),
+            data1,
+            data2
+        ),
+        0xb0 => match gm_controller_name(data1) {
+            Some(name) => format!("CC ch{} {} {} = {}", channel, data1, name, data2),
+            None => format!("CC ch{} {} = {}", channel, data1, data2),
+        },
+        0xc0 => format!("ProgramChange ch{} {}", channel, data1),
+        0xd0 => format!("ChannelPressure ch{} {}", channel, data1),
+        0xe0 => format!(
+            "PitchBend ch{} {}",
+            channel,
+            ((data2 as u16) << 7) | data1 as u16
+        ),
+        _ => match status {
+            0xf0 => format!("SysEx {} bytes", data.len()),
+            0xf1 => format!("MTC Quarter Frame {}", data1),
+            0xf2 => format!("Song Position {}", ((data2 as u16) << 7) | data1 as u16),
+            0xf3 => format!("Song Select {}", data1),
+            0xf6 => "Tune Request".to_string(),
+            0xf8 => "Clock".to_string(),
+            0xfa => "Start".to_string(),
+            0xfb => "Continue".to_string(),
+            0xfc => "Stop".to_string(),
+            0xfe => "Active Sensing".to_string(),
+            0xff => "Reset".to_string(),
+            _ => format!("Unknown {:02X}", status),
+        },
+    }
+}
+
+/// The decoded columns of a message as shown in the debugger
+#[derive(Clone, Debug, Default)]
+pub struct DecodedMsg {
+    pub time: u64,
+    pub port: Option<String>,
+    pub data: Vec<u8>,
+    pub description: String,
+    /// combined value of a 14 bit controller pair or of pitch bend
+    pub value_14bit: Option<u16>,
+    /// time since the previous message
+    pub delta: Option<u64>,
+    /// time since the previous message of the same control
+    pub control_delta: Option<u64>,
+}
+
+impl DecodedMsg {
+    pub fn hex(&self) -> String {
+        hex_bytes(&self.data)
+    }
+}
+
+#[derive(Eq, Hash, PartialEq)]
+enum ControlKey {
+    Address(ControlAddress),
+    Status(u8),
+}
+
+/// Decodes a stream of messages, the deltas and 14 bit values depend on the earlier messages
+#[derive(Default)]
+pub struct MidiDecoder {
+    last_time: Option<u64>,
+    last_control_times: HashMap<ControlKey, u64>,
+    // last value of controllers 0..63 per channel, to combine MSB and LSB
+    controller_values: HashMap<(u8, u8), u8>,
+}
+
+impl MidiDecoder {
+    pub fn reset(&mut self) {
+        *self = MidiDecoder::default();
+    }
+
+    pub fn decode(&mut self, midi_msg: &dyn MidiMsgBase) -> DecodedMsg {
+        let time = midi_msg.get_time();
+        let data = midi_msg.get_data();
+        let status = data.first().copied().unwrap_or(0);
+        let control_key = match midi_msg.get_address() {
+            Some(address) => ControlKey::Address(address),
+            None => ControlKey::Status(status),
+        };
+        let delta = self
+            .last_time
+            .map(|last_time| time.saturating_sub(last_time));
+        let control_delta = self
+            .last_control_times
+            .get(&control_key)
+            .map(|last_time| time.saturating_sub(*last_time));
+        self.last_time = Some(time);
+        self.last_control_times.insert(control_key, time);
+
+        let value_14bit = match (status & 0xf0, data.as_slice()) {
+            (0xb0, [_, controller @ 0..=63, value]) => {
+                let channel = status & 0x0f;
+                self.controller_values
+                    .insert((channel, *controller), *value);
+                let msb_controller = controller % 32;
+                let msb = self.controller_values.get(&(channel, msb_controller));
+                let lsb = self.controller_values.get(&(channel, msb_controller + 32));
+                match (msb, lsb) {
+                    (Some(msb), Some(lsb)) => Some(((*msb as u16) << 7) | *lsb as u16),
+                    _ => None,
+                }
+            }
+            (0xe0, [_, lsb, msb]) => Some(((*msb as u16) << 7) | *lsb as u16),
+            _ => None,
+        };
+        DecodedMsg {
+            time,
+            port: midi_msg.get_port().map(str::to_string),
+            description: describe(&data),
+            data,
+            value_14bit,
+            delta,
+            control_delta,
+        }
+    }
+}
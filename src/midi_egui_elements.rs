@@ -1,4 +1,7 @@
+use crate::control_address::ControlAddress;
+use crate::jackmidi::MidiMsgAdvanced;
 use eframe::{egui, epaint::Stroke};
+use std::hash::Hash;
 
 fn midi_status_indicator_ui(ui: &mut egui::Ui, status: &bool) -> egui::Response {
     let desired_size = ui.spacing().interact_size.y * egui::vec2(2.0, 1.0);
@@ -86,7 +89,7 @@ fn midi_value_indicator_ui(ui: &mut egui::Ui, value: u32) -> egui::Response {
     response
 }
 
-fn midi_note_status_indicator_ui(ui: &mut egui::Ui, id: u32, status: &bool) -> egui::Response {
+fn midi_note_status_indicator_ui(ui: &mut egui::Ui, label: &str, status: &bool) -> egui::Response {
     let width = 8.0;
     let height = 1.0;
     let desired_size = ui.spacing().interact_size.y * egui::vec2(width, height);
@@ -109,13 +112,11 @@ fn midi_note_status_indicator_ui(ui: &mut egui::Ui, id: u32, status: &bool) -> e
             ui.painter().rect(
                 inner_rect,
                 rounding,
-                egui::Color32::from(
-                    egui::Rgba::from(visuals.selection.bg_fill) * color_factor as f32,
-                ),
+                egui::Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * color_factor),
                 Stroke::NONE,
             );
         }
-        let text_id: egui::WidgetText = format!("{}", id).into();
+        let text_id: egui::WidgetText = label.into();
         let galley_note =
             text_id.into_galley(ui, Some(false), f32::INFINITY, egui::TextStyle::Button);
         let text_pos_note = rect.left_center() - egui::Vec2::new(0.0, galley_note.size().y / 2.0)
@@ -142,7 +143,7 @@ fn midi_note_status_indicator_ui(ui: &mut egui::Ui, id: u32, status: &bool) -> e
 
 fn midi_note_status_intensity_indicator_ui(
     ui: &mut egui::Ui,
-    id: u32,
+    label: &str,
     status: &bool,
     intensity: u32,
 ) -> egui::Response {
@@ -172,9 +173,7 @@ fn midi_note_status_intensity_indicator_ui(
             ui.painter().rect(
                 inner_rect,
                 rounding,
-                egui::Color32::from(
-                    egui::Rgba::from(visuals.selection.bg_fill) * color_factor as f32,
-                ),
+                egui::Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * color_factor),
                 Stroke::NONE,
             );
         }
@@ -191,7 +190,7 @@ fn midi_note_status_intensity_indicator_ui(
             egui::Color32::from(egui::Rgba::from(visuals.error_fg_color)),
             Stroke::NONE,
         );
-        let text_id: egui::WidgetText = format!("{}", id).into();
+        let text_id: egui::WidgetText = label.into();
         let galley_note =
             text_id.into_galley(ui, Some(false), f32::INFINITY, egui::TextStyle::Button);
         let text_pos_note = rect.left_center() - egui::Vec2::new(0.0, galley_note.size().y / 2.0)
@@ -282,7 +281,7 @@ fn midi_two_value_indicator_ui(ui: &mut egui::Ui, note: u32, intensity: u32) ->
     response
 }
 
-fn midi_id_value_indicator_ui(ui: &mut egui::Ui, id: u32, intensity: u32) -> egui::Response {
+fn midi_id_value_indicator_ui(ui: &mut egui::Ui, label: &str, intensity: u32) -> egui::Response {
     let intensity: u32 = intensity.min(127);
     let mut fill_level_intensity: f32 = intensity as f32 / 127.0;
     fill_level_intensity = fill_level_intensity.clamp(0.0, 1.0);
@@ -311,7 +310,7 @@ fn midi_id_value_indicator_ui(ui: &mut egui::Ui, id: u32, intensity: u32) -> egu
             egui::Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * color_factor as f32),
             Stroke::NONE,
         );
-        let text_id: egui::WidgetText = format!("{}", id).into();
+        let text_id: egui::WidgetText = label.into();
         let galley_note =
             text_id.into_galley(ui, Some(false), f32::INFINITY, egui::TextStyle::Button);
         let text_intensity: egui::WidgetText =
@@ -346,7 +345,7 @@ fn midi_id_value_indicator_ui(ui: &mut egui::Ui, id: u32, intensity: u32) -> egu
 
 fn midi_id_double_precision_value_indicator_ui(
     ui: &mut egui::Ui,
-    id: u32,
+    label: &str,
     intensity: u32,
 ) -> egui::Response {
     let intensity = intensity.min(16384) as f32 / 16384.0;
@@ -376,7 +375,7 @@ fn midi_id_double_precision_value_indicator_ui(
             egui::Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * color_factor as f32),
             Stroke::NONE,
         );
-        let text_id: egui::WidgetText = format!("{}", id).into();
+        let text_id: egui::WidgetText = label.into();
         let galley_note =
             text_id.into_galley(ui, Some(false), f32::INFINITY, egui::TextStyle::Button);
         let text_intensity: egui::WidgetText =
@@ -417,26 +416,538 @@ pub fn midi_value_indicator(value: u32) -> impl egui::Widget {
     move |ui: &mut egui::Ui| midi_value_indicator_ui(ui, value)
 }
 
-pub fn midi_note_status_indicator(note: u32, status: &bool) -> impl egui::Widget + '_ {
-    move |ui: &mut egui::Ui| midi_note_status_indicator_ui(ui, note, status)
+pub fn midi_note_status_indicator<'a>(label: &'a str, status: &'a bool) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_note_status_indicator_ui(ui, label, status)
 }
 
-pub fn midi_note_status_intensity_indicator(
-    note: u32,
-    status: &bool,
+pub fn midi_note_status_intensity_indicator<'a>(
+    label: &'a str,
+    status: &'a bool,
     intensity: u32,
-) -> impl egui::Widget + '_ {
-    move |ui: &mut egui::Ui| midi_note_status_intensity_indicator_ui(ui, note, status, intensity)
+) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_note_status_intensity_indicator_ui(ui, label, status, intensity)
 }
 
 pub fn midi_two_value_indicator(note: u32, intensity: u32) -> impl egui::Widget {
     move |ui: &mut egui::Ui| midi_two_value_indicator_ui(ui, note, intensity)
 }
 
-pub fn midi_id_value_indicator(note: u32, intensity: u32) -> impl egui::Widget {
-    move |ui: &mut egui::Ui| midi_id_value_indicator_ui(ui, note, intensity)
+pub fn midi_id_value_indicator(label: &str, intensity: u32) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| midi_id_value_indicator_ui(ui, label, intensity)
+}
+
+pub fn midi_id_double_precision_value_indicator(
+    label: &str,
+    intensity: u32,
+) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| midi_id_double_precision_value_indicator_ui(ui, label, intensity)
+}
+
+/// Value range of the interactive widgets
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub enum MidiResolution {
+    #[default]
+    SevenBit,
+    FourteenBit,
+}
+
+impl MidiResolution {
+    pub fn max_value(&self) -> u16 {
+        match self {
+            MidiResolution::SevenBit => 127,
+            MidiResolution::FourteenBit => 16383,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum FaderOrientation {
+    Vertical,
+    Horizontal,
+}
+
+// points to drag for the whole range of a knob or one turn of an encoder
+const DRAG_POINTS_PER_RANGE: f32 = 200.0;
+// the knob arc starts bottom left and ends bottom right
+const KNOB_START_ANGLE: f32 = 0.75 * std::f32::consts::PI;
+const KNOB_SWEEP_ANGLE: f32 = 1.5 * std::f32::consts::PI;
+
+fn paint_centered_text(ui: &egui::Ui, clip_rect: egui::Rect, center: egui::Pos2, text: &str) {
+    let visuals = ui.style().visuals.clone();
+    let text: egui::WidgetText = text.into();
+    let galley = text.into_galley(ui, Some(false), f32::INFINITY, egui::TextStyle::Button);
+    let text_pos = center - galley.size() / 2.0;
+    let text_color = visuals
+        .override_text_color
+        .unwrap_or(visuals.selection.stroke.color);
+    galley.paint_with_fallback_color(
+        &ui.painter().with_clip_rect(clip_rect),
+        text_pos,
+        text_color,
+    );
+}
+
+fn arc_points(
+    center: egui::Pos2,
+    radius: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+) -> Vec<egui::Pos2> {
+    let num_segments = ((sweep_angle.abs() * radius / 4.0).ceil() as usize).max(2);
+    (0..=num_segments)
+        .map(|idx| {
+            let angle = start_angle + sweep_angle * idx as f32 / num_segments as f32;
+            center + radius * egui::vec2(angle.cos(), angle.sin())
+        })
+        .collect()
+}
+
+/// Drags `value` relative to the drag start, keeping the fraction between frames
+fn drag_value(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    value: f32,
+    delta_per_point: egui::Vec2,
+) -> Option<f32> {
+    let id = response.id.with("drag value");
+    if response.drag_started() {
+        ui.data_mut(|data| data.insert_temp(id, value));
+    }
+    if !response.dragged() {
+        return None;
+    }
+    let delta = response.drag_delta() * delta_per_point;
+    let dragged_value = ui.data_mut(|data| {
+        let dragged_value = data.get_temp_mut_or(id, value);
+        *dragged_value += delta.x + delta.y;
+        *dragged_value
+    });
+    Some(dragged_value)
+}
+
+fn midi_fader_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut u16,
+    resolution: MidiResolution,
+    orientation: FaderOrientation,
+) -> egui::Response {
+    let max_value = resolution.max_value();
+    let desired_size = ui.spacing().interact_size.y
+        * match orientation {
+            FaderOrientation::Vertical => egui::vec2(2.0, 8.0),
+            FaderOrientation::Horizontal => egui::vec2(8.0, 1.0),
+        };
+    let (rect, mut response) = ui.allocate_exact_size(desired_size, egui::Sense::click_and_drag());
+    // the fader jumps to the pointer
+    if let Some(pointer_pos) = response.interact_pointer_pos() {
+        let fill_level = match orientation {
+            FaderOrientation::Vertical => (rect.bottom() - pointer_pos.y) / rect.height(),
+            FaderOrientation::Horizontal => (pointer_pos.x - rect.left()) / rect.width(),
+        };
+        let new_value = (fill_level.clamp(0.0, 1.0) * max_value as f32).round() as u16;
+        if new_value != *value {
+            *value = new_value;
+            response.mark_changed();
+        }
+    }
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().visuals.clone();
+        let fill_level = (*value).min(max_value) as f32 / max_value as f32;
+        let rounding = rect.width().min(rect.height()) / 2.0;
+        ui.painter()
+            .rect(rect, rounding, visuals.extreme_bg_color, Stroke::NONE);
+        let (inner_rect, label_pos, value_pos) = match orientation {
+            FaderOrientation::Vertical => (
+                egui::Rect::from_min_max(
+                    egui::pos2(
+                        rect.left(),
+                        (rect.bottom() - rect.height() * fill_level)
+                            .min(rect.bottom() - rect.width()),
+                    ),
+                    rect.max,
+                ),
+                rect.center_top() + egui::vec2(0.0, rect.width() / 2.0),
+                rect.center_bottom() - egui::vec2(0.0, rect.width() / 2.0),
+            ),
+            FaderOrientation::Horizontal => (
+                egui::Rect::from_min_size(
+                    rect.min,
+                    egui::vec2(
+                        (rect.width() * fill_level).max(rect.height()),
+                        rect.height(),
+                    ),
+                ),
+                rect.left_center() + egui::vec2(rect.width() / 4.0, 0.0),
+                rect.center(),
+            ),
+        };
+        // brighter while the fader is used
+        let (dark, bright) = (0.7, 1.0);
+        let color_factor = if response.hovered() || response.dragged() {
+            bright
+        } else {
+            dark
+        };
+        ui.painter().rect(
+            inner_rect,
+            rounding,
+            egui::Color32::from(egui::Rgba::from(visuals.selection.bg_fill) * color_factor),
+            Stroke::NONE,
+        );
+        paint_centered_text(ui, rect, label_pos, label);
+        paint_centered_text(ui, rect, value_pos, &value.to_string());
+    }
+    response
+}
+
+fn midi_knob_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut u16,
+    resolution: MidiResolution,
+) -> egui::Response {
+    let max_value = resolution.max_value();
+    let desired_size = ui.spacing().interact_size.y * egui::vec2(3.0, 3.5);
+    let (rect, mut response) = ui.allocate_exact_size(desired_size, egui::Sense::drag());
+    // dragging up turns the knob right
+    let delta_per_point = egui::vec2(1.0, -1.0) * max_value as f32 / DRAG_POINTS_PER_RANGE;
+    if let Some(dragged_value) = drag_value(ui, &response, *value as f32, delta_per_point) {
+        let new_value = dragged_value.clamp(0.0, max_value as f32).round() as u16;
+        if new_value != *value {
+            *value = new_value;
+            response.mark_changed();
+        }
+    }
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().visuals.clone();
+        let fill_level = (*value).min(max_value) as f32 / max_value as f32;
+        let center = egui::pos2(rect.center().x, rect.top() + rect.width() / 2.0);
+        let radius = rect.width() * 0.4;
+        let stroke_width = radius * 0.25;
+        ui.painter().add(egui::Shape::line(
+            arc_points(center, radius, KNOB_START_ANGLE, KNOB_SWEEP_ANGLE),
+            Stroke::new(stroke_width, visuals.extreme_bg_color),
+        ));
+        let value_angle = KNOB_START_ANGLE + KNOB_SWEEP_ANGLE * fill_level;
+        if fill_level > 0.0 {
+            ui.painter().add(egui::Shape::line(
+                arc_points(
+                    center,
+                    radius,
+                    KNOB_START_ANGLE,
+                    KNOB_SWEEP_ANGLE * fill_level,
+                ),
+                Stroke::new(stroke_width, visuals.selection.bg_fill),
+            ));
+        }
+        let direction = egui::vec2(value_angle.cos(), value_angle.sin());
+        ui.painter().line_segment(
+            [
+                center + direction * radius * 0.3,
+                center + direction * radius,
+            ],
+            ui.style().interact(&response).fg_stroke,
+        );
+        paint_centered_text(ui, rect, center, &value.to_string());
+        paint_centered_text(
+            ui,
+            rect,
+            rect.center_bottom() - egui::vec2(0.0, rect.height() - rect.width()),
+            label,
+        );
+    }
+    response
+}
+
+fn midi_encoder_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    steps: &mut i32,
+    resolution: MidiResolution,
+) -> egui::Response {
+    let steps_per_turn = resolution.max_value() as i32 + 1;
+    let desired_size = ui.spacing().interact_size.y * egui::vec2(3.0, 3.5);
+    let (rect, mut response) = ui.allocate_exact_size(desired_size, egui::Sense::drag());
+    let delta_per_point = egui::vec2(1.0, -1.0) * steps_per_turn as f32 / DRAG_POINTS_PER_RANGE;
+    if let Some(dragged_steps) = drag_value(ui, &response, *steps as f32, delta_per_point) {
+        let new_steps = dragged_steps.round() as i32;
+        if new_steps != *steps {
+            *steps = new_steps;
+            response.mark_changed();
+        }
+    }
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().visuals.clone();
+        let center = egui::pos2(rect.center().x, rect.top() + rect.width() / 2.0);
+        let radius = rect.width() * 0.4;
+        let stroke_width = radius * 0.25;
+        ui.painter().circle_stroke(
+            center,
+            radius,
+            Stroke::new(stroke_width, visuals.extreme_bg_color),
+        );
+        // the marker starts at the top and wraps around
+        let position = steps.rem_euclid(steps_per_turn) as f32 / steps_per_turn as f32;
+        let angle = -0.5 * std::f32::consts::PI + std::f32::consts::TAU * position;
+        ui.painter().circle_filled(
+            center + radius * egui::vec2(angle.cos(), angle.sin()),
+            stroke_width,
+            visuals.selection.bg_fill,
+        );
+        paint_centered_text(ui, rect, center, &format!("{:+}", steps));
+        paint_centered_text(
+            ui,
+            rect,
+            rect.center_bottom() - egui::vec2(0.0, rect.height() - rect.width()),
+            label,
+        );
+    }
+    response
+}
+
+fn midi_xy_pad_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    x: &mut u16,
+    y: &mut u16,
+    resolution: MidiResolution,
+) -> egui::Response {
+    let max_value = resolution.max_value();
+    let desired_size = ui.spacing().interact_size.y * egui::vec2(6.0, 6.0);
+    let (rect, mut response) = ui.allocate_exact_size(desired_size, egui::Sense::click_and_drag());
+    if let Some(pointer_pos) = response.interact_pointer_pos() {
+        let to_value =
+            |fill_level: f32| (fill_level.clamp(0.0, 1.0) * max_value as f32).round() as u16;
+        let new_x = to_value((pointer_pos.x - rect.left()) / rect.width());
+        let new_y = to_value((rect.bottom() - pointer_pos.y) / rect.height());
+        if (new_x, new_y) != (*x, *y) {
+            (*x, *y) = (new_x, new_y);
+            response.mark_changed();
+        }
+    }
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().visuals.clone();
+        ui.painter()
+            .rect(rect, 4.0, visuals.extreme_bg_color, Stroke::NONE);
+        let point = egui::pos2(
+            rect.left() + rect.width() * (*x).min(max_value) as f32 / max_value as f32,
+            rect.bottom() - rect.height() * (*y).min(max_value) as f32 / max_value as f32,
+        );
+        let stroke = Stroke::new(1.0, visuals.selection.bg_fill);
+        ui.painter().line_segment(
+            [
+                egui::pos2(rect.left(), point.y),
+                egui::pos2(rect.right(), point.y),
+            ],
+            stroke,
+        );
+        ui.painter().line_segment(
+            [
+                egui::pos2(point.x, rect.top()),
+                egui::pos2(point.x, rect.bottom()),
+            ],
+            stroke,
+        );
+        ui.painter().circle_filled(
+            point,
+            ui.spacing().interact_size.y / 4.0,
+            visuals.selection.bg_fill,
+        );
+        let text_height = ui.spacing().interact_size.y / 2.0;
+        paint_centered_text(
+            ui,
+            rect,
+            rect.center_top() + egui::vec2(0.0, text_height),
+            label,
+        );
+        paint_centered_text(
+            ui,
+            rect,
+            rect.center_bottom() - egui::vec2(0.0, text_height),
+            &format!("{}, {}", x, y),
+        );
+    }
+    response
+}
+
+/// Fader showing `value` and setting it to the dragged position, `changed` tells about a new value
+pub fn midi_fader<'a>(
+    label: &'a str,
+    value: &'a mut u16,
+    resolution: MidiResolution,
+    orientation: FaderOrientation,
+) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_fader_ui(ui, label, value, resolution, orientation)
 }
 
-pub fn midi_id_double_precision_value_indicator(note: u32, intensity: u32) -> impl egui::Widget {
-    move |ui: &mut egui::Ui| midi_id_double_precision_value_indicator_ui(ui, note, intensity)
+/// Rotary knob with a value arc, dragging up or right turns it up
+pub fn midi_knob<'a>(
+    label: &'a str,
+    value: &'a mut u16,
+    resolution: MidiResolution,
+) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_knob_ui(ui, label, value, resolution)
+}
+
+/// Endless encoder ring, `steps` counts the turned steps, one turn has the steps of the resolution.
+/// The difference to the steps before is the relative value to send.
+pub fn midi_encoder<'a>(
+    label: &'a str,
+    steps: &'a mut i32,
+    resolution: MidiResolution,
+) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_encoder_ui(ui, label, steps, resolution)
+}
+
+/// Pad setting `x` and `y` to the pointer position, `y` counts upwards
+pub fn midi_xy_pad<'a>(
+    label: &'a str,
+    x: &'a mut u16,
+    y: &'a mut u16,
+    resolution: MidiResolution,
+) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| midi_xy_pad_ui(ui, label, x, y, resolution)
+}
+
+/// A pad of a `midi_pad_grid` bound to a note or controller
+#[derive(Clone, Debug)]
+pub struct MidiPad {
+    pub label: String,
+    pub address: Option<ControlAddress>,
+    /// the selection color if not set
+    pub color: Option<egui::Color32>,
+    /// velocity or controller value, 0 is off
+    pub velocity: u8,
+}
+
+impl MidiPad {
+    pub fn new(label: String, address: Option<ControlAddress>) -> Self {
+        MidiPad {
+            label,
+            address,
+            color: None,
+            velocity: 0,
+        }
+    }
+
+    /// Takes the velocity of a note or the value of a controller sent to the pad,
+    /// a pad without port listens to all ports. Returns false for other messages.
+    pub fn update_from_midi_msg(&mut self, midi_msg: &MidiMsgAdvanced) -> bool {
+        let Some(ref pad_address) = self.address else {
+            return false;
+        };
+        let Some(address) = midi_msg.get_address() else {
+            return false;
+        };
+        let same_control = (pad_address.kind, pad_address.channel, pad_address.number)
+            == (address.kind, address.channel, address.number)
+            && (pad_address.port.is_none() || pad_address.port == address.port);
+        if !same_control {
+            return false;
+        }
+        match midi_msg {
+            MidiMsgAdvanced::MidiNoteOnOff(_, on, velocity) => {
+                self.velocity = if *on { *velocity } else { 0 };
+            }
+            MidiMsgAdvanced::MidiControlIdValue(_, value) => {
+                self.velocity = (*value).min(127) as u8;
+            }
+            MidiMsgAdvanced::MidiControl2IdsValue(_, _, value) => {
+                self.velocity = (*value >> 7).min(127) as u8;
+            }
+            MidiMsgAdvanced::MidiEmpty => return false,
+        }
+        true
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum PadEvent {
+    /// the velocity grows towards the top of the pad
+    Pressed {
+        idx: usize,
+        velocity: u8,
+    },
+    Released {
+        idx: usize,
+    },
+}
+
+pub struct PadGridResponse {
+    pub response: egui::Response,
+    pub events: Vec<PadEvent>,
+}
+
+/// Matrix of `pads` with `columns` pads per row, the brightness of a pad shows its velocity.
+/// Pressing and releasing a pad is returned as `PadEvent`, the pads themselves are not changed.
+pub fn midi_pad_grid(
+    ui: &mut egui::Ui,
+    id_source: impl Hash,
+    pads: &[MidiPad],
+    columns: usize,
+) -> PadGridResponse {
+    let id = ui.make_persistent_id(id_source);
+    let columns = columns.max(1);
+    let rows = pads.len().div_ceil(columns);
+    let pad_size = ui.spacing().interact_size.y * egui::vec2(2.5, 2.5);
+    let spacing = ui.spacing().item_spacing;
+    let desired_size = egui::vec2(
+        columns as f32 * (pad_size.x + spacing.x) - spacing.x,
+        rows as f32 * (pad_size.y + spacing.y) - spacing.y,
+    )
+    .max(egui::Vec2::ZERO);
+    let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
+    let mut events = Vec::new();
+    let visuals = ui.style().visuals.clone();
+    for (idx, pad) in pads.iter().enumerate() {
+        let pad_rect = egui::Rect::from_min_size(
+            rect.min
+                + egui::vec2(
+                    (idx % columns) as f32 * (pad_size.x + spacing.x),
+                    (idx / columns) as f32 * (pad_size.y + spacing.y),
+                ),
+            pad_size,
+        );
+        let pad_id = id.with(idx);
+        let mut pad_response = ui.interact(pad_rect, pad_id, egui::Sense::click_and_drag());
+        // a pad is pressed as long as the pointer is down on it
+        let pressed = pad_response.is_pointer_button_down_on();
+        let was_pressed = ui.data_mut(|data| {
+            let was_pressed = data.get_temp(pad_id).unwrap_or(false);
+            data.insert_temp(pad_id, pressed);
+            was_pressed
+        });
+        if pressed && !was_pressed {
+            let velocity = pad_response
+                .interact_pointer_pos()
+                .map_or(127, |pointer_pos| {
+                    let fill_level = (pad_rect.bottom() - pointer_pos.y) / pad_rect.height();
+                    (fill_level.clamp(0.0, 1.0) * 126.0).round() as u8 + 1
+                });
+            events.push(PadEvent::Pressed { idx, velocity });
+        } else if !pressed && was_pressed {
+            events.push(PadEvent::Released { idx });
+        }
+        if let Some(ref address) = pad.address {
+            pad_response = pad_response.on_hover_text(address.to_string());
+        }
+        if ui.is_rect_visible(pad_rect) {
+            let color = pad.color.unwrap_or(visuals.selection.bg_fill);
+            // an off pad is still shown dimmed in its color
+            let brightness = 0.15 + 0.85 * pad.velocity.min(127) as f32 / 127.0;
+            ui.painter().rect(
+                pad_rect,
+                4.0,
+                egui::Color32::from(egui::Rgba::from(color) * brightness),
+                if pressed {
+                    visuals.widgets.active.fg_stroke
+                } else {
+                    Stroke::new(1.0, visuals.extreme_bg_color)
+                },
+            );
+            paint_centered_text(ui, pad_rect, pad_rect.center(), &pad.label);
+        }
+    }
+    PadGridResponse { response, events }
 }
@@ -0,0 +1,181 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::jackmidi::MidiMsgBase;
+use crate::midi_decode::describe;
+
+const MIDI_CLOCK: u8 = 0xf8;
+const MIDI_ACTIVE_SENSING: u8 = 0xfe;
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum MsgCategory {
+    Note,
+    ControlChange,
+    PitchBend,
+    SysEx,
+    Realtime,
+    /// aftertouch, program change and system common messages
+    Other,
+}
+
+impl MsgCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MsgCategory::Note => "note",
+            MsgCategory::ControlChange => "control_change",
+            MsgCategory::PitchBend => "pitch_bend",
+            MsgCategory::SysEx => "sysex",
+            MsgCategory::Realtime => "realtime",
+            MsgCategory::Other => "other",
+        }
+    }
+
+    pub fn from_data(data: &[u8]) -> Self {
+        match data.first() {
+            Some(status) => match status & 0xf0 {
+                0x80 | 0x90 => MsgCategory::Note,
+                0xb0 => MsgCategory::ControlChange,
+                0xe0 => MsgCategory::PitchBend,
+                0xf0 if *status == 0xf0 => MsgCategory::SysEx,
+                0xf0 if *status >= 0xf8 => MsgCategory::Realtime,
+                _ => MsgCategory::Other,
+            },
+            None => MsgCategory::Other,
+        }
+    }
+}
+
+/// Decides which messages the debugger shows.
+/// Channel, note and controller ranges only apply to channel messages.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct MidiFilter {
+    /// bit n set shows channel n (0..15)
+    pub channel_mask: u16,
+    pub show_note: bool,
+    pub show_control_change: bool,
+    pub show_pitch_bend: bool,
+    pub show_sysex: bool,
+    pub show_realtime: bool,
+    pub show_other: bool,
+    pub hide_clock_and_active_sensing: bool,
+    pub note_min: u8,
+    pub note_max: u8,
+    pub controller_min: u8,
+    pub controller_max: u8,
+    /// case insensitive search in the displayed text of a message
+    pub search_text: String,
+}
+
+impl Default for MidiFilter {
+    fn default() -> Self {
+        MidiFilter {
+            channel_mask: 0xffff,
+            show_note: true,
+            show_control_change: true,
+            show_pitch_bend: true,
+            show_sysex: true,
+            show_realtime: true,
+            show_other: true,
+            hide_clock_and_active_sensing: true,
+            note_min: 0,
+            note_max: 127,
+            controller_min: 0,
+            controller_max: 127,
+            search_text: String::new(),
+        }
+    }
+}
+
+impl MidiFilter {
+    pub fn is_channel_shown(&self, channel: u8) -> bool {
+        self.channel_mask & (1 << (channel & 0x0f)) != 0
+    }
+
+    pub fn set_channel_shown(&mut self, channel: u8, shown: bool) {
+        if shown {
+            self.channel_mask |= 1 << (channel & 0x0f);
+        } else {
+            self.channel_mask &= !(1 << (channel & 0x0f));
+        }
+    }
+
+    pub fn matches(&self, midi_msg: &dyn MidiMsgBase) -> bool {
+        let data = midi_msg.get_data();
+        let category = MsgCategory::from_data(&data);
+        let category_shown = match category {
+            MsgCategory::Note => self.show_note,
+            MsgCategory::ControlChange => self.show_control_change,
+            MsgCategory::PitchBend => self.show_pitch_bend,
+            MsgCategory::SysEx => self.show_sysex,
+            MsgCategory::Realtime => self.show_realtime,
+            MsgCategory::Other => self.show_other,
+        };
+        if !category_shown {
+            return false;
+        }
+        let status = data.first().copied().unwrap_or(0);
+        if self.hide_clock_and_active_sensing
+            && (status == MIDI_CLOCK || status == MIDI_ACTIVE_SENSING)
+        {
+            return false;
+        }
+        if (0x80..0xf0).contains(&status) && !self.is_channel_shown(status & 0x0f) {
+            return false;
+        }
+        let number = data.get(1).copied().unwrap_or(0);
+        let number_in_range = match category {
+            MsgCategory::Note => (self.note_min..=self.note_max).contains(&number),
+            MsgCategory::ControlChange => {
+                (self.controller_min..=self.controller_max).contains(&number)
+            }
+            _ => true,
+        };
+        if !number_in_range {
+            return false;
+        }
+        if self.search_text.is_empty() {
+            return true;
+        }
+        // the debugger shows both the message and its decoded description
+        let search_text = self.search_text.to_lowercase();
+        midi_msg.to_string().to_lowercase().contains(&search_text)
+            || describe(&data).to_lowercase().contains(&search_text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jackmidi::midi_msg_from_bytes;
+
+    fn search(search_text: &str, data: &[u8]) -> bool {
+        let midi_filter = MidiFilter {
+            search_text: search_text.to_string(),
+            ..Default::default()
+        };
+        midi_filter.matches(midi_msg_from_bytes(data, 0).as_ref())
+    }
+
+    #[test]
+    fn search_in_decoded_description() {
+        assert!(search("sustain", &[0xb0, 64, 127]));
+        assert!(search("C#4", &[0x90, 61, 100]));
+        assert!(search("ch3", &[0x82, 61, 0]));
+        assert!(!search("sustain", &[0xb0, 7, 127]));
+        assert!(!search("C#4", &[0x90, 60, 100]));
+    }
+}
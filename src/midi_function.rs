@@ -1,12 +1,14 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::Read;
+use std::fmt;
 use std::result::Result;
-use std::{fmt, fs::File};
 
-use crate::jackmidi::MidiMsgAdvanced;
+use crate::control_address::ControlAddress;
+use crate::file_format::{parse_file, write_file};
+use crate::midi_mapping::parse_file_to_midi_mapping;
 
-#[derive(Hash, Eq, PartialEq, Serialize, Deserialize, Clone, Debug)]
+#[derive(Hash, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct MidiFunction {
     name: String,
     // ToDo: invert, log,linear, scaling..
@@ -19,6 +21,9 @@ impl MidiFunction {
     pub fn get_name(&self) -> String {
         self.name.as_str().to_string()
     }
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
 }
 
 impl fmt::Display for MidiFunction {
@@ -29,53 +34,40 @@ impl fmt::Display for MidiFunction {
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct MidiFunctionFile {
     pub midi_functions: Vec<MidiFunction>,
 }
 
-pub fn parse_json_file_to_midi_functions(
-    file_path_str: &String,
-) -> Result<MidiFunctionFile, String> {
-    let mut file_content =
-        File::open(file_path_str).map_err(|err| format!("Could not read the json file {}", err))?;
-    let mut contents = String::new();
-    file_content
-        .read_to_string(&mut contents)
-        .map_err(|err| format!("Could not read file to string {}", err))?;
-    let module: MidiFunctionFile = serde_json::from_str(contents.as_str())
-        .map_err(|err| format!("error in json deserialize {}", err))?;
-    Ok(module)
+/// Read a json, toml or yaml file with the list of midi functions
+pub fn parse_file_to_midi_functions(file_path_str: &str) -> Result<MidiFunctionFile, String> {
+    parse_file(file_path_str)
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-pub struct MidiFunctionWithElementsFile {
-    pub midi_functions: HashMap<String, Vec<u16>>,
+pub fn write_midi_functions_to_file(
+    file_path_str: &str,
+    midi_functions: &MidiFunctionFile,
+) -> Result<(), String> {
+    write_file(file_path_str, midi_functions)
 }
 
-pub fn parse_json_file_to_midi_functions_with_elements_ids(
-    file_path_str: &String,
-) -> Result<HashMap<String, Vec<u16>>, String> {
-    let mut file_content =
-        File::open(file_path_str).map_err(|err| format!("Could not read the json file {}", err))?;
-    let mut contents = String::new();
-    file_content
-        .read_to_string(&mut contents)
-        .map_err(|err| format!("Could not read file to string {}", err))?;
-    let map: HashMap<String, Vec<u16>> = serde_json::from_str(contents.as_str()).unwrap();
-    Ok(map)
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MidiFunctionWithElementsFile {
+    pub midi_functions: HashMap<String, Vec<ControlAddress>>,
 }
 
-pub fn parse_json_file_to_midi_functions_with_midi_msgs_advanced(
-    file_path_str: &String,
-) -> Result<HashMap<String, Vec<MidiMsgAdvanced>>, String> {
-    let mut file_content =
-        File::open(file_path_str).map_err(|err| format!("Could not read the json file {}", err))?;
-    let mut contents = String::new();
-    file_content
-        .read_to_string(&mut contents)
-        .map_err(|err| format!("Could not read file to string {}", err))?;
-    let map: HashMap<String, Vec<MidiMsgAdvanced>> =
-        serde_json::from_str(contents.as_str()).unwrap();
+pub fn parse_file_to_midi_functions_with_elements_ids(
+    file_path_str: &str,
+) -> Result<HashMap<String, Vec<ControlAddress>>, String> {
+    // the mapping parser also migrates the old files with packed ids
+    let midi_mapping = parse_file_to_midi_mapping(file_path_str)?;
+    let map = midi_mapping
+        .midi_functions
+        .into_iter()
+        .map(|(name, bindings)| {
+            let addresses = bindings.iter().flat_map(|binding| binding.addresses());
+            (name, addresses.collect())
+        })
+        .collect();
     Ok(map)
 }
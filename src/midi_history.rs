@@ -0,0 +1,252 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::jackmidi::MidiMsgBase;
+use crate::midi_decode::DecodedMsg;
+use std::collections::VecDeque;
+use std::fmt;
+
+pub const DEFAULT_HISTORY_LEN: usize = 10_000;
+
+pub enum HistoryEntry {
+    Msg(Box<dyn MidiMsgBase>, DecodedMsg),
+    /// user annotation, e.g. "pressed PLAY here"
+    Marker {
+        time: u64,
+        text: String,
+    },
+}
+
+impl HistoryEntry {
+    pub fn get_time(&self) -> u64 {
+        match self {
+            HistoryEntry::Msg(midi_msg, _) => midi_msg.get_time(),
+            HistoryEntry::Marker { time, .. } => *time,
+        }
+    }
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryEntry::Msg(midi_msg, _) => write!(f, "{}", midi_msg),
+            HistoryEntry::Marker { time, text } => write!(f, "---- {} ---- time: {}", text, time),
+        }
+    }
+}
+
+/// Ring buffer of the last `max_len` entries.
+/// Every entry gets an increasing id, so views can keep referring to entries
+/// while old ones are dropped.
+/// While paused new entries are held back, so the shown ones stay in place.
+pub struct MidiHistory {
+    entries: VecDeque<HistoryEntry>,
+    first_id: usize,
+    max_len: usize,
+    paused: bool,
+    paused_entries: VecDeque<HistoryEntry>,
+}
+
+impl Default for MidiHistory {
+    fn default() -> Self {
+        MidiHistory::new(DEFAULT_HISTORY_LEN)
+    }
+}
+
+impl MidiHistory {
+    pub fn new(max_len: usize) -> Self {
+        MidiHistory {
+            entries: VecDeque::new(),
+            first_id: 0,
+            max_len: max_len.max(1),
+            paused: false,
+            paused_entries: VecDeque::new(),
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len.max(1);
+        self.drop_old_entries();
+    }
+
+    /// Add an entry, while paused it is held back until `resume`
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.paused {
+            self.paused_entries.push_back(entry);
+        } else {
+            self.entries.push_back(entry);
+        }
+        self.drop_old_entries();
+    }
+
+    pub fn push_marker(&mut self, time: u64, text: String) {
+        self.push(HistoryEntry::Marker { time, text })
+    }
+
+    fn drop_old_entries(&mut self) {
+        while self.entries.len() > self.max_len {
+            self.entries.pop_front();
+            self.first_id += 1;
+        }
+        // the ring buffer would drop the older ones anyway on resume
+        while self.paused_entries.len() > self.max_len {
+            self.paused_entries.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.first_id = self.end_id();
+        self.entries.clear();
+        self.paused_entries.clear();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Add the entries held back while paused
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.entries.extend(self.paused_entries.drain(..));
+        self.drop_old_entries();
+    }
+
+    /// number of entries held back while paused
+    pub fn paused_len(&self) -> usize {
+        self.paused_entries.len()
+    }
+
+    /// id of the oldest entry still kept
+    pub fn first_id(&self) -> usize {
+        self.first_id
+    }
+
+    /// id the next entry will get
+    pub fn end_id(&self) -> usize {
+        self.first_id + self.entries.len()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&HistoryEntry> {
+        self.entries.get(id.checked_sub(self.first_id)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// entries with their ids, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &HistoryEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (self.first_id + idx, entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(time: u64) -> HistoryEntry {
+        HistoryEntry::Marker {
+            time,
+            text: format!("marker {}", time),
+        }
+    }
+
+    fn times(midi_history: &MidiHistory) -> Vec<(usize, u64)> {
+        midi_history
+            .iter()
+            .map(|(id, entry)| (id, entry.get_time()))
+            .collect()
+    }
+
+    #[test]
+    fn ring_buffer_keeps_ids() {
+        let mut midi_history = MidiHistory::new(3);
+        for time in 0..5 {
+            midi_history.push(marker(time));
+        }
+        assert_eq!(times(&midi_history), vec![(2, 2), (3, 3), (4, 4)]);
+        assert!(midi_history.get(1).is_none());
+        assert_eq!(midi_history.get(3).unwrap().get_time(), 3);
+        midi_history.clear();
+        assert!(midi_history.is_empty());
+        assert_eq!(midi_history.first_id(), 5);
+        assert_eq!(midi_history.end_id(), 5);
+    }
+
+    #[test]
+    fn pause_holds_back_entries() {
+        let mut midi_history = MidiHistory::new(10);
+        midi_history.push(marker(0));
+        midi_history.pause();
+        midi_history.push(marker(1));
+        midi_history.push_marker(2, "pressed PLAY here".to_string());
+        assert!(midi_history.is_paused());
+        assert_eq!(times(&midi_history), vec![(0, 0)]);
+        assert_eq!(midi_history.paused_len(), 2);
+
+        midi_history.resume();
+        assert!(!midi_history.is_paused());
+        assert_eq!(midi_history.paused_len(), 0);
+        assert_eq!(times(&midi_history), vec![(0, 0), (1, 1), (2, 2)]);
+        assert!(matches!(
+            midi_history.get(2),
+            Some(HistoryEntry::Marker { text, .. }) if text == "pressed PLAY here"
+        ));
+    }
+
+    #[test]
+    fn pause_keeps_only_max_len_entries() {
+        let mut midi_history = MidiHistory::new(3);
+        midi_history.push(marker(0));
+        midi_history.push(marker(1));
+        midi_history.pause();
+        for time in 2..7 {
+            midi_history.push(marker(time));
+        }
+        assert_eq!(times(&midi_history), vec![(0, 0), (1, 1)]);
+        assert_eq!(midi_history.paused_len(), 3);
+
+        midi_history.resume();
+        assert_eq!(times(&midi_history), vec![(2, 4), (3, 5), (4, 6)]);
+    }
+
+    #[test]
+    fn clear_drops_paused_entries() {
+        let mut midi_history = MidiHistory::new(3);
+        midi_history.pause();
+        midi_history.push(marker(0));
+        midi_history.clear();
+        midi_history.resume();
+        assert!(midi_history.is_empty());
+        assert_eq!(midi_history.paused_len(), 0);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::control_address::ControlAddress;
+use crate::control_classifier::{ControlClassifier, ControlGuess};
+use crate::jackmidi::MidiMsgAdvanced;
+use crate::midi_mapping::MidiBinding;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// movement in 7 bit steps a control needs before it is learned
+pub const DEFAULT_LEARN_THRESHOLD: u16 = 8;
+
+/// Binds the next moved control to the armed function.
+/// With several functions they are armed one after the other,
+/// controls learned in the session are not learned again.
+#[derive(Default, Clone, Debug)]
+pub struct MidiLearn {
+    pub threshold: u16,
+    functions: VecDeque<String>,
+    learned_addresses: HashSet<ControlAddress>,
+    // first 7 bit value of each control since the current function was armed
+    start_values: HashMap<ControlAddress, u16>,
+    control_classifiers: HashMap<ControlAddress, ControlClassifier>,
+}
+
+impl MidiLearn {
+    pub fn new(function_names: Vec<String>) -> Self {
+        MidiLearn {
+            threshold: DEFAULT_LEARN_THRESHOLD,
+            functions: function_names.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The function the next moved control is bound to
+    pub fn armed_function(&self) -> Option<&str> {
+        self.functions.front().map(String::as_str)
+    }
+
+    /// Number of functions still to learn, including the armed one
+    pub fn remaining(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Arms the next function without binding the current one
+    pub fn skip(&mut self) {
+        self.functions.pop_front();
+        self.start_values.clear();
+        self.control_classifiers.clear();
+    }
+
+    /// Returns the armed function and its binding when `midi_msg` moved a control past the threshold.
+    /// Notes are learned on note on, 14 bit controls on their paired message.
+    pub fn push(&mut self, midi_msg: &MidiMsgAdvanced) -> Option<(String, MidiBinding)> {
+        self.armed_function()?;
+        let address = midi_msg.get_address()?;
+        if self.learned_addresses.contains(address) {
+            return None;
+        }
+        let control_classifier = self.control_classifiers.entry(address.clone()).or_default();
+        control_classifier.push(midi_msg);
+        let control_guess = control_classifier.guess();
+        let moved = match midi_msg {
+            MidiMsgAdvanced::MidiEmpty => false,
+            MidiMsgAdvanced::MidiNoteOnOff(_, on, intensity) => *on && *intensity > 0,
+            // the MSB alone of a 14 bit control is followed by the paired message
+            MidiMsgAdvanced::MidiControlIdValue(_, _)
+                if control_guess == Some(ControlGuess::FaderOrKnob14Bit) =>
+            {
+                false
+            }
+            MidiMsgAdvanced::MidiControlIdValue(_, _)
+                if matches!(control_guess, Some(ControlGuess::Encoder(_))) =>
+            {
+                true
+            }
+            MidiMsgAdvanced::MidiControlIdValue(_, value) => {
+                self.moved_past_threshold(address, *value)
+            }
+            MidiMsgAdvanced::MidiControl2IdsValue(_, _, value) => {
+                self.moved_past_threshold(address, *value >> 7)
+            }
+        };
+        if !moved {
+            return None;
+        }
+        let mut binding = MidiBinding::from_midi_msg_advanced(midi_msg)?;
+        binding.mode = control_guess.map(|control_guess| control_guess.binding_mode());
+        self.learned_addresses.insert(address.clone());
+        let function_name = self.functions.front().cloned()?;
+        self.skip();
+        Some((function_name, binding))
+    }
+
+    fn moved_past_threshold(&mut self, address: &ControlAddress, value: u16) -> bool {
+        let start_value = *self.start_values.entry(address.clone()).or_insert(value);
+        start_value.abs_diff(value) >= self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_mapping::{BindingControl, BindingMode};
+
+    fn control_change(controller: u8, value: u16) -> MidiMsgAdvanced {
+        MidiMsgAdvanced::MidiControlIdValue(ControlAddress::control_change(0, controller), value)
+    }
+
+    fn function_names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn learn_functions_one_after_the_other() {
+        let mut midi_learn = MidiLearn::new(function_names(&["Volume", "Pan", "Filter"]));
+        assert_eq!(midi_learn.armed_function(), Some("Volume"));
+        assert_eq!(midi_learn.remaining(), 3);
+
+        // below the threshold
+        assert!(midi_learn.push(&control_change(7, 60)).is_none());
+        assert!(midi_learn.push(&control_change(7, 62)).is_none());
+        let (function_name, binding) = midi_learn.push(&control_change(7, 68)).unwrap();
+        assert_eq!(function_name, "Volume");
+        assert_eq!(
+            binding.control,
+            BindingControl::Cc {
+                channel: 1,
+                controller: 7
+            }
+        );
+        assert_eq!(binding.mode, Some(BindingMode::Absolute));
+        assert_eq!(midi_learn.armed_function(), Some("Pan"));
+
+        // a learned control is not learned again
+        assert!(midi_learn.push(&control_change(7, 0)).is_none());
+        assert!(midi_learn.push(&control_change(7, 127)).is_none());
+
+        midi_learn.skip();
+        assert_eq!(midi_learn.armed_function(), Some("Filter"));
+        assert_eq!(midi_learn.remaining(), 1);
+
+        let note_on = MidiMsgAdvanced::MidiNoteOnOff(ControlAddress::note(0, 36), true, 100);
+        let (function_name, binding) = midi_learn.push(&note_on).unwrap();
+        assert_eq!(function_name, "Filter");
+        assert_eq!(
+            binding.control,
+            BindingControl::Note {
+                channel: 1,
+                note: 36
+            }
+        );
+        assert!(midi_learn.is_done());
+        assert_eq!(midi_learn.armed_function(), None);
+        let other_note_on = MidiMsgAdvanced::MidiNoteOnOff(ControlAddress::note(0, 37), true, 100);
+        assert!(midi_learn.push(&other_note_on).is_none());
+    }
+
+    #[test]
+    fn movement_starts_again_when_the_next_function_is_armed() {
+        let mut midi_learn = MidiLearn::new(function_names(&["Volume", "Pan"]));
+        assert!(midi_learn.push(&control_change(10, 60)).is_none());
+        midi_learn.skip();
+        assert!(midi_learn.push(&control_change(10, 66)).is_none());
+        let (function_name, _) = midi_learn.push(&control_change(10, 74)).unwrap();
+        assert_eq!(function_name, "Pan");
+    }
+
+    #[test]
+    fn learn_14_bit_control_on_paired_message() {
+        let mut midi_learn = MidiLearn::new(function_names(&["Cutoff"]));
+        let paired = |value: u16| {
+            MidiMsgAdvanced::MidiControl2IdsValue(
+                ControlAddress::control_change(0, 1),
+                ControlAddress::control_change(0, 33),
+                value,
+            )
+        };
+        assert!(midi_learn.push(&paired(0)).is_none());
+        let (function_name, binding) = midi_learn.push(&paired(8 << 7)).unwrap();
+        assert_eq!(function_name, "Cutoff");
+        assert_eq!(
+            binding.control,
+            BindingControl::Cc14 {
+                channel: 1,
+                msb_controller: 1,
+                lsb_controller: 33
+            }
+        );
+        assert!(midi_learn.is_done());
+    }
+}
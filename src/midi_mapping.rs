@@ -0,0 +1,634 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::control_address::{AddressKind, ControlAddress};
+use crate::file_format::{parse_file, write_file};
+use crate::jackmidi::MidiMsgAdvanced;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const MIDI_MAPPING_VERSION: u32 = 2;
+
+/// The kind of control a binding listens to.
+/// Channels are counted from 1 to 16 like on the devices.
+#[derive(
+    Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BindingControl {
+    Note {
+        #[schemars(range(min = 1, max = 16))]
+        channel: u8,
+        #[schemars(range(max = 127))]
+        note: u8,
+    },
+    Cc {
+        #[schemars(range(min = 1, max = 16))]
+        channel: u8,
+        #[schemars(range(max = 127))]
+        controller: u8,
+    },
+    Cc14 {
+        #[schemars(range(min = 1, max = 16))]
+        channel: u8,
+        #[schemars(range(max = 127))]
+        msb_controller: u8,
+        #[schemars(range(max = 127))]
+        lsb_controller: u8,
+    },
+    PitchBend {
+        #[schemars(range(min = 1, max = 16))]
+        channel: u8,
+    },
+}
+
+impl BindingControl {
+    pub fn channel(&self) -> u8 {
+        match *self {
+            BindingControl::Note { channel, .. }
+            | BindingControl::Cc { channel, .. }
+            | BindingControl::Cc14 { channel, .. }
+            | BindingControl::PitchBend { channel } => channel,
+        }
+    }
+
+    /// Fields out of the midi range with their problem, e.g. `("channel", "channel 17 out of range 1..16")`
+    pub fn range_issues(&self) -> Vec<(&'static str, String)> {
+        let mut range_issues = Vec::new();
+        let channel = self.channel();
+        if !(1..=16).contains(&channel) {
+            range_issues.push(("channel", format!("channel {} out of range 1..16", channel)));
+        }
+        let numbers = match *self {
+            BindingControl::Note { note, .. } => vec![("note", note)],
+            BindingControl::Cc { controller, .. } => vec![("controller", controller)],
+            BindingControl::Cc14 {
+                msb_controller,
+                lsb_controller,
+                ..
+            } => vec![
+                ("msb_controller", msb_controller),
+                ("lsb_controller", lsb_controller),
+            ],
+            BindingControl::PitchBend { .. } => Vec::new(),
+        };
+        for (field, number) in numbers {
+            if number > 127 {
+                range_issues.push((field, format!("{} {} out of range 0..127", field, number)));
+            }
+        }
+        range_issues
+    }
+}
+
+/// How the values of a bound control are interpreted
+#[derive(
+    Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingMode {
+    /// the value is the position of a fader or knob
+    Absolute,
+    /// on while pressed, off when released
+    Momentary,
+    /// encoder sending 1.. for up and 127.. for down
+    RelativeTwosComplement,
+    /// encoder sending 65.. for up and 63.. for down
+    RelativeOffset,
+}
+
+impl BindingMode {
+    pub const ALL: [BindingMode; 4] = [
+        BindingMode::Absolute,
+        BindingMode::Momentary,
+        BindingMode::RelativeTwosComplement,
+        BindingMode::RelativeOffset,
+    ];
+
+    /// Signed step of a relative encoder from its 7 bit value
+    pub fn relative_delta(&self, value: u8) -> Option<i8> {
+        let value = value & 0x7f;
+        match self {
+            // 7 bit two's complement, 127 is -1
+            BindingMode::RelativeTwosComplement => Some(((value << 1) as i8) >> 1),
+            BindingMode::RelativeOffset => Some(value as i8 - 64),
+            _ => None,
+        }
+    }
+
+    /// 7 bit value a relative encoder sends for the signed step, the reverse of `relative_delta`
+    pub fn relative_value(&self, delta: i8) -> Option<u8> {
+        let delta = delta.clamp(-64, 63);
+        match self {
+            BindingMode::RelativeTwosComplement => Some(delta as u8 & 0x7f),
+            BindingMode::RelativeOffset => Some((delta + 64) as u8),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BindingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BindingMode::Absolute => "absolute",
+            BindingMode::Momentary => "momentary",
+            BindingMode::RelativeTwosComplement => "relative 1/127",
+            BindingMode::RelativeOffset => "relative 63/65",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single midi control bound to a function.
+/// `mode` is not part of the control, bindings differing only in `mode` listen to the same messages.
+#[derive(
+    Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, JsonSchema, Clone, Debug,
+)]
+pub struct MidiBinding {
+    #[serde(flatten)]
+    pub control: BindingControl,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<BindingMode>,
+}
+
+impl MidiBinding {
+    pub fn from_address(address: &ControlAddress) -> Self {
+        let channel = address.channel + 1;
+        let control = match address.kind {
+            AddressKind::Note => BindingControl::Note {
+                channel,
+                note: address.number,
+            },
+            AddressKind::ControlChange => BindingControl::Cc {
+                channel,
+                controller: address.number,
+            },
+            AddressKind::PitchBend => BindingControl::PitchBend { channel },
+        };
+        MidiBinding {
+            control,
+            port: address.port.clone(),
+            device: address.device.clone(),
+            mode: None,
+        }
+    }
+
+    pub fn from_address_pair(msb_address: &ControlAddress, lsb_address: &ControlAddress) -> Self {
+        let mut binding = Self::from_address(msb_address);
+        if let BindingControl::Cc {
+            channel,
+            controller,
+        } = binding.control
+        {
+            if lsb_address.kind == AddressKind::ControlChange && msb_address != lsb_address {
+                binding.control = BindingControl::Cc14 {
+                    channel,
+                    msb_controller: controller,
+                    lsb_controller: lsb_address.number,
+                };
+            }
+        }
+        binding
+    }
+
+    pub fn from_midi_msg_advanced(midi_msg: &MidiMsgAdvanced) -> Option<Self> {
+        match midi_msg {
+            MidiMsgAdvanced::MidiEmpty => None,
+            MidiMsgAdvanced::MidiNoteOnOff(address, _, _) => Some(Self::from_address(address)),
+            MidiMsgAdvanced::MidiControlIdValue(address, _) => Some(Self::from_address(address)),
+            MidiMsgAdvanced::MidiControl2IdsValue(address0, address1, _) => {
+                Some(Self::from_address_pair(address0, address1))
+            }
+        }
+    }
+
+    /// The binding without its mode, to look up the bindings of a message
+    pub fn without_mode(&self) -> Self {
+        MidiBinding {
+            mode: None,
+            ..self.clone()
+        }
+    }
+
+    /// Raw midi messages the bound control sends for `value`.
+    /// `value` is 7 bit (the velocity of a note, 0 is note off) or 14 bit for 14 bit pairs
+    /// and pitch bend, 14 bit pairs send the MSB and then the LSB.
+    pub fn midi_messages(&self, value: u16) -> Vec<Vec<u8>> {
+        let status = |status: u8| status | (self.control.channel().saturating_sub(1) & 0x0f);
+        let value7 = value.min(127) as u8;
+        let value14 = value.min(16383);
+        let (msb, lsb) = ((value14 >> 7) as u8, (value14 & 0x7f) as u8);
+        match self.control {
+            BindingControl::Note { note, .. } if value7 > 0 => {
+                vec![vec![status(0x90), note & 0x7f, value7]]
+            }
+            BindingControl::Note { note, .. } => vec![vec![status(0x80), note & 0x7f, 0]],
+            BindingControl::Cc { controller, .. } => {
+                vec![vec![status(0xb0), controller & 0x7f, value7]]
+            }
+            BindingControl::Cc14 {
+                msb_controller,
+                lsb_controller,
+                ..
+            } => vec![
+                vec![status(0xb0), msb_controller & 0x7f, msb],
+                vec![status(0xb0), lsb_controller & 0x7f, lsb],
+            ],
+            BindingControl::PitchBend { .. } => vec![vec![status(0xe0), lsb, msb]],
+        }
+    }
+
+    /// All control addresses the binding listens to, the MSB first for 14 bit controls
+    pub fn addresses(&self) -> Vec<ControlAddress> {
+        let with_origin = |address: ControlAddress| {
+            address
+                .with_port(self.port.clone())
+                .with_device(self.device.clone())
+        };
+        match self.control {
+            BindingControl::Note { channel, note } => {
+                vec![with_origin(ControlAddress::note(
+                    channel.saturating_sub(1),
+                    note,
+                ))]
+            }
+            BindingControl::Cc {
+                channel,
+                controller,
+            } => vec![with_origin(ControlAddress::control_change(
+                channel.saturating_sub(1),
+                controller,
+            ))],
+            BindingControl::Cc14 {
+                channel,
+                msb_controller,
+                lsb_controller,
+            } => vec![
+                with_origin(ControlAddress::control_change(
+                    channel.saturating_sub(1),
+                    msb_controller,
+                )),
+                with_origin(ControlAddress::control_change(
+                    channel.saturating_sub(1),
+                    lsb_controller,
+                )),
+            ],
+            BindingControl::PitchBend { channel } => {
+                vec![with_origin(ControlAddress::pitch_bend(
+                    channel.saturating_sub(1),
+                ))]
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for MidiBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let addresses = self.addresses();
+        match addresses.as_slice() {
+            [msb_address, lsb_address] => write!(f, "{}+cc{}", msb_address, lsb_address.number)?,
+            _ => write!(f, "{}", addresses[0])?,
+        }
+        match self.mode {
+            Some(mode) => write!(f, " ({})", mode),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Versioned mapping of function names to their midi bindings.
+/// Functions are kept in a `BTreeMap` so the written file is sorted by name.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct MidiMappingFile {
+    pub version: u32,
+    pub midi_functions: BTreeMap<String, Vec<MidiBinding>>,
+}
+
+impl Default for MidiMappingFile {
+    fn default() -> Self {
+        MidiMappingFile {
+            version: MIDI_MAPPING_VERSION,
+            midi_functions: BTreeMap::new(),
+        }
+    }
+}
+
+impl MidiMappingFile {
+    pub fn from_bindings<'a, I>(midi_functions: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a String, &'a Vec<MidiBinding>)>,
+    {
+        let mut mapping = MidiMappingFile::default();
+        for (name, bindings) in midi_functions {
+            mapping
+                .midi_functions
+                .insert(name.clone(), bindings.clone());
+        }
+        mapping
+    }
+
+    /// Read any known mapping format and migrate it to the current version
+    pub fn from_json_value(value: Value) -> Result<Self, String> {
+        let Value::Object(map) = value else {
+            return Err("mapping file has to be a json object".to_string());
+        };
+        if is_versioned_mapping(&map) {
+            let version = &map["version"];
+            if version.as_u64() != Some(MIDI_MAPPING_VERSION as u64) {
+                return Err(format!("unsupported mapping file version {}", version));
+            }
+            let mapping: MidiMappingFile = serde_json::from_value(Value::Object(map))
+                .map_err(|err| format!("error in json deserialize {}", err))?;
+            // out of range values would be masked to another control
+            for (name, bindings) in mapping.midi_functions.iter() {
+                for (idx, binding) in bindings.iter().enumerate() {
+                    if let Some((_, range_issue)) = binding.control.range_issues().first() {
+                        return Err(format!(
+                            "binding {} of function {}: {}",
+                            idx, name, range_issue
+                        ));
+                    }
+                }
+            }
+            return Ok(mapping);
+        }
+        // version 1: function name -> list of packed ids or of MidiMsgAdvanced
+        let mut mapping = MidiMappingFile::default();
+        for (name, entries) in map {
+            let Value::Array(entries) = entries else {
+                return Err(format!("bindings of function {} are not a list", name));
+            };
+            let mut bindings = Vec::new();
+            for entry in entries {
+                let binding = migrate_v1_entry(&entry)
+                    .ok_or_else(|| format!("can't migrate entry {} of function {}", entry, name))?;
+                bindings.push(binding);
+            }
+            mapping.midi_functions.insert(name, bindings);
+        }
+        Ok(mapping)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Versioned files have a `version` next to `midi_functions`,
+/// in old files every value is a list of bindings, even of a function named "version"
+pub(crate) fn is_versioned_mapping(map: &serde_json::Map<String, Value>) -> bool {
+    map.get("version")
+        .is_some_and(|version| !version.is_array())
+}
+
+pub(crate) fn migrate_v1_entry(entry: &Value) -> Option<MidiBinding> {
+    let address = |id: &Value| ControlAddress::from_legacy_id(u16::try_from(id.as_u64()?).ok()?);
+    if entry.is_u64() {
+        return Some(MidiBinding::from_address(&address(entry)?));
+    }
+    // old serialization of MidiMsgAdvanced, e.g. {"MidiControlIdValue":[45057,0]}
+    let (variant, fields) = entry.as_object()?.iter().next()?;
+    match variant.as_str() {
+        "MidiNoteOnOff" | "MidiControlIdValue" => {
+            Some(MidiBinding::from_address(&address(fields.get(0)?)?))
+        }
+        "MidiControl2IdsValue" => Some(MidiBinding::from_address_pair(
+            &address(fields.get(0)?)?,
+            &address(fields.get(1)?)?,
+        )),
+        _ => None,
+    }
+}
+
+/// Read a json, toml or yaml mapping file and migrate it to the current version
+pub fn parse_file_to_midi_mapping(file_path_str: &str) -> Result<MidiMappingFile, String> {
+    let value: Value = parse_file(file_path_str)?;
+    MidiMappingFile::from_json_value(value)
+}
+
+pub fn write_midi_mapping_to_file(
+    file_path_str: &str,
+    mapping: &MidiMappingFile,
+) -> Result<(), String> {
+    write_file(file_path_str, mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrate_v1_mapping_to_v2_and_back() {
+        let v1_mapping = json!({
+            "Volume": [45319, {"MidiControlIdValue": [45319, 0]}],
+            "Play": [{"MidiNoteOnOff": [36906, 32810, false]}],
+            "Jog": [{"MidiControl2IdsValue": [45057, 45089, 0]}],
+            "Bend": [58112],
+        });
+        let midi_mapping = MidiMappingFile::from_json_value(v1_mapping).unwrap();
+        assert_eq!(midi_mapping.version, MIDI_MAPPING_VERSION);
+        let binding = |control| MidiBinding {
+            control,
+            port: None,
+            device: None,
+            mode: None,
+        };
+        let cc7 = binding(BindingControl::Cc {
+            channel: 2,
+            controller: 7,
+        });
+        assert_eq!(
+            midi_mapping.midi_functions["Volume"],
+            vec![cc7.clone(), cc7]
+        );
+        assert_eq!(
+            midi_mapping.midi_functions["Play"],
+            vec![binding(BindingControl::Note {
+                channel: 1,
+                note: 42
+            })]
+        );
+        assert_eq!(
+            midi_mapping.midi_functions["Jog"],
+            vec![binding(BindingControl::Cc14 {
+                channel: 1,
+                msb_controller: 1,
+                lsb_controller: 33
+            })]
+        );
+        assert_eq!(
+            midi_mapping.midi_functions["Bend"],
+            vec![binding(BindingControl::PitchBend { channel: 4 })]
+        );
+
+        let v2_mapping: Value = serde_json::from_str(&midi_mapping.to_json_string()).unwrap();
+        assert_eq!(
+            v2_mapping["midi_functions"]["Jog"],
+            json!([{"type": "cc14", "channel": 1, "msb_controller": 1, "lsb_controller": 33}])
+        );
+        assert_eq!(
+            MidiMappingFile::from_json_value(v2_mapping).unwrap(),
+            midi_mapping
+        );
+    }
+
+    #[test]
+    fn relative_delta_of_encoder_values() {
+        let twos_complement = BindingMode::RelativeTwosComplement;
+        assert_eq!(twos_complement.relative_delta(1), Some(1));
+        assert_eq!(twos_complement.relative_delta(63), Some(63));
+        assert_eq!(twos_complement.relative_delta(64), Some(-64));
+        assert_eq!(twos_complement.relative_delta(127), Some(-1));
+        let offset = BindingMode::RelativeOffset;
+        assert_eq!(offset.relative_delta(65), Some(1));
+        assert_eq!(offset.relative_delta(64), Some(0));
+        assert_eq!(offset.relative_delta(63), Some(-1));
+        assert_eq!(offset.relative_delta(0), Some(-64));
+        assert_eq!(BindingMode::Absolute.relative_delta(1), None);
+        assert_eq!(BindingMode::Momentary.relative_delta(127), None);
+    }
+
+    #[test]
+    fn relative_value_is_reverse_of_relative_delta() {
+        for mode in [
+            BindingMode::RelativeTwosComplement,
+            BindingMode::RelativeOffset,
+        ] {
+            for delta in -64..=63 {
+                let value = mode.relative_value(delta).unwrap();
+                assert!(value <= 127);
+                assert_eq!(
+                    mode.relative_delta(value),
+                    Some(delta),
+                    "{} {}",
+                    mode,
+                    delta
+                );
+            }
+            for value in 0..=127 {
+                let delta = mode.relative_delta(value).unwrap();
+                assert_eq!(
+                    mode.relative_value(delta),
+                    Some(value),
+                    "{} {}",
+                    mode,
+                    value
+                );
+            }
+            // larger steps are clamped to the 7 bit range
+            assert_eq!(mode.relative_value(100), mode.relative_value(63));
+            assert_eq!(mode.relative_value(-100), mode.relative_value(-64));
+        }
+        assert_eq!(BindingMode::Absolute.relative_value(1), None);
+    }
+
+    #[test]
+    fn midi_messages_of_bindings() {
+        let binding = |control| MidiBinding {
+            control,
+            port: None,
+            device: None,
+            mode: None,
+        };
+        let cc14 = binding(BindingControl::Cc14 {
+            channel: 3,
+            msb_controller: 1,
+            lsb_controller: 33,
+        });
+        // 0x2aab = MSB 0x55, LSB 0x2b
+        assert_eq!(
+            cc14.midi_messages(0x2aab),
+            vec![vec![0xb2, 1, 0x55], vec![0xb2, 33, 0x2b]]
+        );
+        assert_eq!(
+            cc14.midi_messages(u16::MAX),
+            vec![vec![0xb2, 1, 0x7f], vec![0xb2, 33, 0x7f]]
+        );
+        let pitch_bend = binding(BindingControl::PitchBend { channel: 16 });
+        // LSB first
+        assert_eq!(
+            pitch_bend.midi_messages(0x2aab),
+            vec![vec![0xef, 0x2b, 0x55]]
+        );
+        assert_eq!(pitch_bend.midi_messages(8192), vec![vec![0xef, 0, 64]]);
+        // read back by the input side
+        let midi_msg =
+            crate::jackmidi::midi_msg_from_bytes(&pitch_bend.midi_messages(0x2aab)[0], 0);
+        assert_eq!(midi_msg.get_value(), 0x2aab);
+        let note = binding(BindingControl::Note {
+            channel: 1,
+            note: 36,
+        });
+        assert_eq!(note.midi_messages(100), vec![vec![0x90, 36, 100]]);
+        assert_eq!(note.midi_messages(0), vec![vec![0x80, 36, 0]]);
+    }
+
+    #[test]
+    fn reject_unknown_v1_entry_and_version() {
+        assert!(MidiMappingFile::from_json_value(json!({"Volume": [1234]})).is_err());
+        assert!(MidiMappingFile::from_json_value(json!({"Volume": [{"MidiEmpty": []}]})).is_err());
+        assert!(
+            MidiMappingFile::from_json_value(json!({"version": 3, "midi_functions": {}})).is_err()
+        );
+    }
+
+    #[test]
+    fn reject_out_of_range_v2_bindings() {
+        let mapping = |binding| json!({"version": 2, "midi_functions": {"Volume": [binding]}});
+        let err = MidiMappingFile::from_json_value(mapping(
+            json!({"type": "cc", "channel": 17, "controller": 7}),
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "binding 0 of function Volume: channel 17 out of range 1..16"
+        );
+        for binding in [
+            json!({"type": "cc", "channel": 0, "controller": 7}),
+            json!({"type": "note", "channel": 1, "note": 128}),
+            json!({"type": "cc14", "channel": 1, "msb_controller": 1, "lsb_controller": 200}),
+        ] {
+            assert!(MidiMappingFile::from_json_value(mapping(binding)).is_err());
+        }
+        assert!(MidiMappingFile::from_json_value(mapping(
+            json!({"type": "pitch_bend", "channel": 16})
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn v1_function_named_version() {
+        let midi_mapping =
+            MidiMappingFile::from_json_value(json!({"version": [45319], "Volume": [45320]}))
+                .unwrap();
+        assert_eq!(
+            midi_mapping.midi_functions["version"][0].control,
+            BindingControl::Cc {
+                channel: 2,
+                controller: 7
+            }
+        );
+        assert_eq!(midi_mapping.midi_functions.len(), 2);
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::jackmidi::MidiMsgBase;
+use crate::midi_capture::MidiCaptureEvent;
+
+use std::time::{Duration, Instant};
+
+/// Plays captured midi events into the same channel `start_jack_thread` feeds.
+/// With `realtime` the original timing is kept, otherwise events are sent as fast as
+/// the receiver takes them. The sender is dropped at the end of the capture.
+pub fn start_midi_player_thread(
+    rx_close: crossbeam_channel::Receiver<bool>,
+    midi_sender: std::sync::mpsc::SyncSender<Box<dyn MidiMsgBase>>,
+    midi_capture_events: Vec<MidiCaptureEvent>,
+    realtime: bool,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        // a false or a dropped sender on rx_close stops the playback
+        let wait_for_close = |timeout: Duration| {
+            matches!(
+                rx_close.recv_timeout(timeout),
+                Ok(false) | Err(crossbeam_channel::RecvTimeoutError::Disconnected)
+            )
+        };
+        let start_instant = Instant::now();
+        let start_time = midi_capture_events.first().map_or(0, |event| event.time);
+        for midi_capture_event in midi_capture_events.iter() {
+            let offset = Duration::from_micros(midi_capture_event.time - start_time);
+            let wait = if realtime {
+                offset.saturating_sub(start_instant.elapsed())
+            } else {
+                Duration::ZERO
+            };
+            if wait_for_close(wait) {
+                return;
+            }
+            // no message gets lost in fast mode, a full channel is retried
+            let mut midi_msg = midi_capture_event.to_midi_msg();
+            loop {
+                match midi_sender.try_send(midi_msg) {
+                    Ok(()) => break,
+                    Err(std::sync::mpsc::TrySendError::Full(unsent_midi_msg)) => {
+                        midi_msg = unsent_midi_msg;
+                        if wait_for_close(Duration::from_millis(1)) {
+                            return;
+                        }
+                    }
+                    Err(std::sync::mpsc::TrySendError::Disconnected(_)) => return,
+                }
+            }
+        }
+    })
+}
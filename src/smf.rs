@@ -0,0 +1,233 @@
+/*
+ * This file is part of the rust_midi_mapper distribution (https://github.com/majorx234/rust_midi_mapper ).
+ * Copyright (c) 2023-2024 Majorx234 <majorx234@googlemail.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 3.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::midi_capture::MidiCaptureEvent;
+use midly::{
+    live::LiveEvent,
+    num::{u15, u24, u28},
+    Format, Header, MetaMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+// 120 bpm with 1000 ticks per quarter note gives a resolution of 500us
+const TICKS_PER_QUARTER: u16 = 1000;
+// also the default tempo of files without tempo event
+const TEMPO_US_PER_QUARTER: u32 = 500_000;
+const US_PER_TICK: u64 = (TEMPO_US_PER_QUARTER / TICKS_PER_QUARTER as u32) as u64;
+
+fn track_event_kind(data: &[u8]) -> Option<TrackEventKind<'_>> {
+    match data.first()? {
+        // the leading 0xF0 is implicit in the file
+        0xf0 => Some(TrackEventKind::SysEx(&data[1..])),
+        _ => match LiveEvent::parse(data).ok()? {
+            LiveEvent::Midi { channel, message } => Some(TrackEventKind::Midi { channel, message }),
+            // system common and real-time messages can't be stored in a file
+            _ => None,
+        },
+    }
+}
+
+/// Write the events as single track (type 0) standard midi file.
+/// Delta times are derived from the event timestamps relative to the first event.
+pub fn write_smf_type0(
+    file_path_str: &str,
+    midi_capture_events: &[MidiCaptureEvent],
+) -> Result<(), String> {
+    let mut track = vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(TEMPO_US_PER_QUARTER))),
+    }];
+    let start_time = midi_capture_events.first().map_or(0, |event| event.time);
+    let mut last_tick = 0;
+    for midi_capture_event in midi_capture_events {
+        let Some(kind) = track_event_kind(&midi_capture_event.data) else {
+            continue;
+        };
+        // round the absolute time, so errors don't add up over the deltas
+        let tick =
+            (midi_capture_event.time.saturating_sub(start_time) + US_PER_TICK / 2) / US_PER_TICK;
+        let delta = tick.saturating_sub(last_tick);
+        last_tick = last_tick.max(tick);
+        track.push(TrackEvent {
+            delta: u28::new(delta.min(u28::max_value().as_int() as u64) as u32),
+            kind,
+        });
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    let smf = Smf {
+        header: Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(TICKS_PER_QUARTER)),
+        ),
+        tracks: vec![track],
+    };
+    smf.save(file_path_str)
+        .map_err(|err| format!("Could not write the midi file {}", err))
+}
+
+/// Read all tracks of a standard midi file into one list of events sorted by time.
+/// Timestamps are in microseconds from the start of the file, tempo changes are applied.
+pub fn read_smf(file_path_str: &str) -> Result<Vec<MidiCaptureEvent>, String> {
+    let raw = std::fs::read(file_path_str)
+        .map_err(|err| format!("Could not read the midi file {}", err))?;
+    let smf = Smf::parse(&raw).map_err(|err| format!("Could not parse the midi file {}", err))?;
+    // merge the tracks by absolute ticks, the sort is stable so the order in a track is kept
+    let mut track_events: Vec<(u64, TrackEventKind)> = Vec::new();
+    for track in smf.tracks.iter() {
+        let mut tick: u64 = 0;
+        for track_event in track.iter() {
+            tick += track_event.delta.as_int() as u64;
+            track_events.push((tick, track_event.kind));
+        }
+    }
+    track_events.sort_by_key(|(tick, _)| *tick);
+
+    let ticks_per_quarter = match smf.header.timing {
+        Timing::Metrical(ticks_per_quarter) => Some(ticks_per_quarter.as_int().max(1) as f64),
+        Timing::Timecode(_, _) => None,
+    };
+    let mut us_per_tick = match smf.header.timing {
+        Timing::Metrical(_) => TEMPO_US_PER_QUARTER as f64 / ticks_per_quarter.unwrap(),
+        Timing::Timecode(fps, subframes) => 1_000_000.0 / (fps.as_f32() as f64 * subframes as f64),
+    };
+    let mut time = 0.0;
+    let mut last_tick = 0;
+    let mut midi_capture_events = Vec::new();
+    for (tick, kind) in track_events {
+        time += (tick - last_tick) as f64 * us_per_tick;
+        last_tick = tick;
+        let data = match kind {
+            TrackEventKind::Midi { channel, message } => {
+                let mut data = Vec::new();
+                LiveEvent::Midi { channel, message }
+                    .write_std(&mut data)
+                    .map_err(|err| format!("Could not convert midi event {}", err))?;
+                data
+            }
+            TrackEventKind::SysEx(sysex_data) => [&[0xf0], sysex_data].concat(),
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                if let Some(ticks_per_quarter) = ticks_per_quarter {
+                    us_per_tick = tempo.as_int() as f64 / ticks_per_quarter;
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        midi_capture_events.push(MidiCaptureEvent {
+            time: time.round() as u64,
+            data,
+            port: None,
+        });
+    }
+    Ok(midi_capture_events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}.mid", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn capture_event(time: u64, data: &[u8]) -> MidiCaptureEvent {
+        MidiCaptureEvent {
+            time,
+            data: data.to_vec(),
+            port: None,
+        }
+    }
+
+    #[test]
+    fn write_and_read_smf() {
+        let file_path = temp_file_path("write_and_read_smf");
+        let start_time = 5_000_123;
+        let midi_capture_events = vec![
+            capture_event(start_time, &[0x90, 60, 100]),
+            // clock can't be stored and is dropped
+            capture_event(start_time + 100, &[0xf8]),
+            capture_event(start_time + 1_249, &[0xb3, 64, 127]),
+            capture_event(start_time + 2_251, &[0xe0, 0x00, 0x40]),
+            capture_event(start_time + 2_300, &[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]),
+            capture_event(start_time + 1_000_000, &[0x80, 60, 0]),
+        ];
+        write_smf_type0(&file_path, &midi_capture_events).unwrap();
+        let read_events = read_smf(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        // times start at 0 and are rounded to 500us ticks
+        let expected_events = [
+            capture_event(0, &[0x90, 60, 100]),
+            capture_event(1_000, &[0xb3, 64, 127]),
+            capture_event(2_500, &[0xe0, 0x00, 0x40]),
+            capture_event(2_500, &[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]),
+            capture_event(1_000_000, &[0x80, 60, 0]),
+        ];
+        assert_eq!(read_events.len(), expected_events.len());
+        for (read_event, expected_event) in read_events.iter().zip(expected_events.iter()) {
+            assert_eq!(read_event.time, expected_event.time);
+            assert_eq!(read_event.data, expected_event.data);
+        }
+    }
+
+    #[test]
+    fn read_smf_with_tempo_change() {
+        let file_path = temp_file_path("read_smf_with_tempo_change");
+        let note_on = |delta: u32, key: u8| TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Midi {
+                channel: 0.into(),
+                message: midly::MidiMessage::NoteOn {
+                    key: key.into(),
+                    vel: 100.into(),
+                },
+            },
+        };
+        let tempo = |delta: u32, us_per_quarter: u32| TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(us_per_quarter))),
+        };
+        // 96 ticks per quarter, starts with the default tempo of 120 bpm
+        let track = vec![
+            note_on(96, 60),
+            tempo(0, 1_000_000),
+            note_on(48, 62),
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ];
+        let smf = Smf {
+            header: Header::new(Format::SingleTrack, Timing::Metrical(u15::new(96))),
+            tracks: vec![track],
+        };
+        smf.save(&file_path).unwrap();
+        let read_events = read_smf(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(read_events.len(), 2);
+        assert_eq!(read_events[0].time, 500_000);
+        assert_eq!(read_events[0].data, vec![0x90, 60, 100]);
+        assert_eq!(read_events[1].time, 1_000_000);
+        assert_eq!(read_events[1].data, vec![0x90, 62, 100]);
+    }
+}